@@ -0,0 +1,450 @@
+use crate::{DexOptInfo, Package};
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Oat headers live at the start of `.rodata`; the key-value store follows within a few KiB.
+const OAT_PREFIX: u64 = 1 << 20;
+
+/// Boot images ART may load, most specific first: the one compiled after a mainline update wins.
+const BOOT_IMAGE_DIRS: &[&str] = &[
+    "/data/misc/apexdata/com.android.art/dalvik-cache",
+    "/apex/com.android.art/javalib",
+    "/system/framework",
+];
+
+fn u32_at(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+/// `"027\0"` -> `027`.
+fn version(bytes: &[u8]) -> String {
+    bytes.iter().take_while(|b| **b != 0).map(|b| *b as char).collect()
+}
+
+/// The part of a `.vdex` header that ties it to the APK it was compiled from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VdexHeader {
+    pub version: String,
+    /// One checksum per dex file, in `classes.dex`, `classes2.dex`, ... order.
+    pub dex_checksums: Vec<u32>,
+}
+
+impl VdexHeader {
+    /// Parses the sectioned layout of Android 12+ (`027`) and the flat one of Android 10/11 (`019`, `021`);
+    /// other versions are refused rather than read with a guessed layout.
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.get(..4) != Some(b"vdex") {
+            bail!("not a vdex file");
+        }
+        let version = version(bytes.get(4..8).context("truncated vdex header")?);
+        let (offset, count) = match version.parse::<u32>() {
+            Ok(27) => {
+                const CHECKSUM_SECTION: u32 = 0;
+                let sections = u32_at(bytes, 8).context("truncated vdex header")?;
+                let section = (0..sections as usize)
+                    .map(|i| 12 + i * 12)
+                    .find(|&at| u32_at(bytes, at) == Some(CHECKSUM_SECTION))
+                    .context("vdex has no checksum section")?;
+                let offset = u32_at(bytes, section + 4).context("truncated vdex section header")?;
+                let size = u32_at(bytes, section + 8).context("truncated vdex section header")?;
+                (offset as usize, size as usize / 4)
+            }
+            // magic, verifier deps version, dex section version, dex count, deps size, then two more sizes from 021
+            Ok(21) => (28, u32_at(bytes, 12).context("truncated vdex header")? as usize),
+            Ok(19) => (20, u32_at(bytes, 12).context("truncated vdex header")? as usize),
+            _ => bail!("unsupported vdex version '{}'", version),
+        };
+        let dex_checksums = (0..count)
+            .map(|i| u32_at(bytes, offset + i * 4))
+            .collect::<Option<Vec<u32>>>()
+            .context("truncated vdex checksums")?;
+        Ok(VdexHeader { version, dex_checksums })
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let mut bytes = Vec::new();
+        File::open(path)
+            .and_then(|f| f.take(64 * 1024).read_to_end(&mut bytes))
+            .with_context(|| format!("Failed to read '{}'", path.display()))?;
+        Self::parse(&bytes)
+    }
+}
+
+/// The oat header of an `.odex`: its version, dex file count and key-value store.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OatHeader {
+    pub version: String,
+    pub dex_file_count: u32,
+    pub store: BTreeMap<String, String>,
+}
+
+/// The fixed part of the oat header of one range of oat versions.
+struct OatLayout {
+    versions: (u32, u32),
+    /// Offset of `key_value_store_size_`; the store itself follows it.
+    key_value_store_size: usize,
+}
+
+/// Every layout starts with magic, version, `oat_checksum_`, `instruction_set_`, the ISA feature
+/// bitmap and `dex_file_count_`; the trampoline offsets after them change between releases.
+/// Android 10 (`170`) is left out: its layout has not been checked against a device.
+const OAT_LAYOUTS: &[OatLayout] = &[
+    // Android 11: interpreter bridges are gone, the boot image checksum moved into `bootclasspath-checksums`
+    OatLayout { versions: (183, 183), key_value_store_size: 52 },
+    // Android 12: critical native and nterp trampolines
+    OatLayout { versions: (195, 199), key_value_store_size: 60 },
+    // Android 13 and later ART modules: `bcp_bss_info_offset_`
+    OatLayout { versions: (225, u32::MAX), key_value_store_size: 64 },
+];
+
+/// Offset of `dex_file_count_`, the same in every supported layout.
+const OAT_DEX_FILE_COUNT: usize = 20;
+
+/// Splits a key-value store: `key\0value\0` pairs, where newer releases pad some values with more NULs.
+fn parse_store(bytes: &[u8]) -> Option<BTreeMap<String, String>> {
+    let mut store = BTreeMap::new();
+    let mut fields = bytes.split(|b| *b == 0);
+    while let Some(key) = fields.next() {
+        if key.is_empty() {
+            // Padding, or the end of the store
+            continue;
+        }
+        let value = fields.next()?;
+        store.insert(std::str::from_utf8(key).ok()?.to_string(), String::from_utf8_lossy(value).into_owned());
+    }
+    Some(store)
+}
+
+impl OatHeader {
+    /// Finds the header in an ELF `.odex` prefix and reads it with the layout of its oat version.
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.get(..4) != Some(b"\x7fELF") {
+            bail!("not an ELF file");
+        }
+        // .rodata, which starts with the oat header, is page aligned (4 KiB or 16 KiB pages)
+        let start = (0..bytes.len())
+            .step_by(4096)
+            .find(|&at| {
+                bytes.get(at..at + 8).is_some_and(|h| h.starts_with(b"oat\n") && h[4..7].iter().all(u8::is_ascii_digit) && h[7] == 0)
+            })
+            .context("no oat header found")?;
+        let header = &bytes[start..];
+        let version = version(&header[4..8]);
+        let layout = version
+            .parse::<u32>()
+            .ok()
+            .and_then(|v| OAT_LAYOUTS.iter().find(|l| (l.versions.0..=l.versions.1).contains(&v)))
+            .with_context(|| format!("unsupported oat version '{}'", version))?;
+
+        let dex_file_count = u32_at(header, OAT_DEX_FILE_COUNT).context("truncated oat header")?;
+        let size = u32_at(header, layout.key_value_store_size).context("truncated oat header")? as usize;
+        let at = layout.key_value_store_size + 4;
+        let store = header
+            .get(at..at + size)
+            .context("truncated oat key-value store")
+            .and_then(|s| parse_store(s).context("malformed oat key-value store"))?;
+        Ok(OatHeader { version, dex_file_count, store })
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let mut bytes = Vec::new();
+        File::open(path)
+            .and_then(|f| f.take(OAT_PREFIX).read_to_end(&mut bytes))
+            .with_context(|| format!("Failed to read '{}'", path.display()))?;
+        Self::parse(&bytes)
+    }
+
+    /// Checksums of the boot classpath the code was compiled against, e.g. `i;9/1f2e3d4c:i;2/...`.
+    pub fn bootclasspath_checksums(&self) -> Option<&str> {
+        self.store.get("bootclasspath-checksums").map(String::as_str)
+    }
+}
+
+/// Locates the central directory from the end-of-central-directory record at the end of `tail`.
+fn central_directory(tail: &[u8]) -> Result<(u64, usize)> {
+    let eocd = (0..tail.len().saturating_sub(21))
+        .rev()
+        .find(|&at| u32_at(tail, at) == Some(0x0605_4b50))
+        .context("no end of central directory record")?;
+    let size = u32_at(tail, eocd + 12).context("truncated zip")?;
+    let offset = u32_at(tail, eocd + 16).context("truncated zip")?;
+    if offset == u32::MAX {
+        bail!("zip64 archives are not supported");
+    }
+    Ok((offset as u64, size as usize))
+}
+
+/// CRC-32 of every entry, which ART uses as the checksum of dex files inside an APK.
+fn entry_crcs(directory: &[u8]) -> BTreeMap<String, u32> {
+    let mut crcs = BTreeMap::new();
+    let mut at = 0;
+    while u32_at(directory, at) == Some(0x0201_4b50) {
+        let (Some(crc), Some(name_len), Some(extra_len), Some(comment_len)) =
+            (u32_at(directory, at + 16), u16_at(directory, at + 28), u16_at(directory, at + 30), u16_at(directory, at + 32))
+        else {
+            break;
+        };
+        let Some(name) = directory.get(at + 46..at + 46 + name_len as usize) else {
+            break;
+        };
+        crcs.insert(String::from_utf8_lossy(name).into_owned(), crc);
+        at += 46 + name_len as usize + extra_len as usize + comment_len as usize;
+    }
+    crcs
+}
+
+/// `classes.dex`, `classes2.dex`, ... checksums, stopping at the first gap like ART does.
+fn dex_checksums(crcs: &BTreeMap<String, u32>) -> Vec<u32> {
+    (1..)
+        .map(|i| if i == 1 { "classes.dex".to_string() } else { format!("classes{}.dex", i) })
+        .map_while(|name| crcs.get(&name).copied())
+        .collect()
+}
+
+/// Dex checksums of an APK, read from its central directory only.
+pub fn apk_dex_checksums(path: &Path) -> Result<Vec<u32>> {
+    let read = || -> std::io::Result<(File, Vec<u8>)> {
+        let mut file = File::open(path)?;
+        let len = file.seek(SeekFrom::End(0))?;
+        // The record is 22 bytes plus a comment of up to 64 KiB
+        let tail_len = len.min(22 + 0xffff);
+        file.seek(SeekFrom::Start(len - tail_len))?;
+        let mut tail = vec![0; tail_len as usize];
+        file.read_exact(&mut tail)?;
+        Ok((file, tail))
+    };
+    let (mut file, tail) = read().with_context(|| format!("Failed to read '{}'", path.display()))?;
+    let (offset, size) = central_directory(&tail).with_context(|| format!("Invalid APK '{}'", path.display()))?;
+
+    let mut directory = vec![0; size];
+    file.seek(SeekFrom::Start(offset))
+        .and_then(|_| file.read_exact(&mut directory))
+        .with_context(|| format!("Failed to read the central directory of '{}'", path.display()))?;
+    Ok(dex_checksums(&entry_crcs(&directory)))
+}
+
+/// The primary boot image's entry in a boot classpath checksum string: `i;<components>/<image checksum>`.
+pub fn boot_image_checksum(header: &[u8]) -> Result<String> {
+    if header.get(..4) != Some(b"art\n") {
+        bail!("not a boot image");
+    }
+    let components = u32_at(header, 12).context("truncated image header")?;
+    let checksum = u32_at(header, 24).context("truncated image header")?;
+    Ok(format!("i;{}/{:08x}", components, checksum))
+}
+
+/// Checksum of the boot image the runtime loads for `isa`, if one can be found.
+pub fn device_boot_image_checksum(isa: &str) -> Option<String> {
+    BOOT_IMAGE_DIRS.iter().find_map(|dir| {
+        let mut header = [0u8; 32];
+        File::open(Path::new(dir).join(isa).join("boot.art")).ok()?.read_exact(&mut header).ok()?;
+        boot_image_checksum(&header).ok()
+    })
+}
+
+/// Why ART would not use an artifact.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Problem {
+    /// The APK gained or lost dex files since compilation.
+    DexCount { artifact: usize, apk: usize },
+    /// A dex file changed since compilation.
+    DexChecksum { dex: String, artifact: String, apk: String },
+    /// The boot image changed (system or ART module update) since compilation.
+    BootClasspath { artifact: String, device: String },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::DexCount { artifact, apk } => write!(f, "compiled for {} dex files, the APK has {}", artifact, apk),
+            Problem::DexChecksum { dex, artifact, apk } => write!(f, "{} changed (checksum {} in vdex, {} in APK)", dex, artifact, apk),
+            Problem::BootClasspath { artifact, device } => {
+                write!(f, "compiled against boot image {}, device has {}", artifact, device)
+            }
+        }
+    }
+}
+
+/// The verdict for one dexopt entry's artifacts.
+#[derive(Debug, Clone, Serialize)]
+pub struct ArtifactCheck<'a> {
+    pub package: &'a str,
+    pub isa: &'a str,
+    pub status: &'a str,
+    pub vdex: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vdex_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oat_version: Option<String>,
+    pub problems: Vec<Problem>,
+    /// Why part of the check could not be done, e.g. an oat version without a known layout.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unverified: Vec<String>,
+}
+
+/// Output of `dexter verify --format json`.
+#[derive(Debug, Serialize)]
+pub struct VerifyReport<'a> {
+    pub checked: usize,
+    /// Boot image checksum per ISA, `null` when no boot image was found.
+    pub boot_images: BTreeMap<&'a str, Option<String>>,
+    pub rejected: Vec<&'a ArtifactCheck<'a>>,
+    /// Artifacts with no problem found but a header that could not be read.
+    pub unverified: Vec<&'a ArtifactCheck<'a>>,
+}
+
+impl ArtifactCheck<'_> {
+    pub fn is_rejected(&self) -> bool {
+        !self.problems.is_empty()
+    }
+
+    /// Not rejected, but not fully checked either.
+    pub fn is_unverified(&self) -> bool {
+        !self.is_rejected() && !self.unverified.is_empty()
+    }
+}
+
+/// Compares the dex and boot classpath checksums recorded in one entry's artifacts with the APK and
+/// `boot_image` (see [`device_boot_image_checksum`]); `None` when the entry has no vdex to check.
+pub fn check<'a>(pkg: &'a Package, info: &'a DexOptInfo, boot_image: Option<&str>) -> Option<ArtifactCheck<'a>> {
+    let odex = info.artifact_path(pkg)?;
+    let vdex = odex.with_extension("vdex");
+    if !vdex.exists() {
+        return None;
+    }
+    let mut result = ArtifactCheck {
+        package: &pkg.name,
+        isa: &info.isa,
+        status: &info.status,
+        vdex: vdex.display().to_string(),
+        vdex_version: None,
+        oat_version: None,
+        problems: Vec::new(),
+        unverified: Vec::new(),
+    };
+
+    let apk = Path::new(info.dex_path.as_deref().unwrap_or(&pkg.path));
+    let mut apk_dex_count = None;
+    match VdexHeader::read(&vdex).and_then(|header| Ok((apk_dex_checksums(apk)?, header))) {
+        Ok((apk_checksums, header)) => {
+            apk_dex_count = Some(apk_checksums.len());
+            if header.dex_checksums.len() != apk_checksums.len() {
+                result.problems.push(Problem::DexCount { artifact: header.dex_checksums.len(), apk: apk_checksums.len() });
+            } else if let Some(i) = (0..apk_checksums.len()).find(|&i| header.dex_checksums[i] != apk_checksums[i]) {
+                result.problems.push(Problem::DexChecksum {
+                    dex: if i == 0 { "classes.dex".to_string() } else { format!("classes{}.dex", i + 1) },
+                    artifact: format!("{:08x}", header.dex_checksums[i]),
+                    apk: format!("{:08x}", apk_checksums[i]),
+                });
+            }
+            result.vdex_version = Some(header.version);
+        }
+        Err(e) => result.unverified.push(format!("{:#}", e)),
+    }
+
+    // Filters without compiled code may come without an .odex
+    if odex.exists() {
+        match OatHeader::read(&odex) {
+            Ok(header) => {
+                // Reported once, by the vdex, when both disagree with the APK
+                if let Some(apk) = apk_dex_count
+                    && header.dex_file_count as usize != apk
+                    && !result.problems.iter().any(|p| matches!(p, Problem::DexCount { .. }))
+                {
+                    result.problems.push(Problem::DexCount { artifact: header.dex_file_count as usize, apk });
+                }
+                // Only the primary boot image is compared; extensions depend on the class loader context
+                let recorded = header.bootclasspath_checksums().and_then(|c| c.split(':').next());
+                if let (Some(recorded), Some(device)) = (recorded, boot_image)
+                    && recorded.starts_with("i;")
+                    && recorded != device
+                {
+                    result.problems.push(Problem::BootClasspath { artifact: recorded.to_string(), device: device.to_string() });
+                }
+                result.oat_version = Some(header.version);
+            }
+            Err(e) => result.unverified.push(format!("{:#}", e)),
+        }
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_headers() {
+        let vdex = VdexHeader::parse(include_bytes!("../tests/fixtures/android14.vdex")).unwrap();
+        assert_eq!(vdex.version, "027");
+        assert_eq!(vdex.dex_checksums, [0x9c1e_77a2, 0x4b0d_2e15]);
+        let vdex = VdexHeader::parse(include_bytes!("../tests/fixtures/android11.vdex")).unwrap();
+        assert_eq!(vdex.dex_checksums, [0x3e5a_1f07]);
+        assert!(VdexHeader::parse(b"vdex099\0").is_err());
+
+        // Padded `apex-versions` value, so a naive split would misalign the pairs
+        let oat = OatHeader::parse(include_bytes!("../tests/fixtures/android14.odex")).unwrap();
+        assert_eq!((oat.version.as_str(), oat.dex_file_count), ("230", 2));
+        assert_eq!(oat.store["apex-versions"], "/341111000/341111000");
+        assert_eq!(oat.store["compiler-filter"], "speed-profile");
+        assert_eq!(oat.bootclasspath_checksums(), Some("i;9/7a3c61b5:d/2c0f3e8a"));
+
+        let oat = OatHeader::parse(include_bytes!("../tests/fixtures/android11.odex")).unwrap();
+        assert_eq!((oat.version.as_str(), oat.dex_file_count), ("183", 1));
+        assert_eq!(oat.store["compiler-filter"], "verify");
+        assert_eq!(oat.store.len(), 8);
+
+        // Android 10 and versions between the supported releases have no known layout
+        let mut unsupported = include_bytes!("../tests/fixtures/android11.odex").to_vec();
+        for version in [b"170\0", b"200\0"] {
+            unsupported[4096 + 4..4096 + 8].copy_from_slice(version);
+            assert!(OatHeader::parse(&unsupported).unwrap_err().to_string().contains("unsupported oat version"));
+        }
+
+        let image = include_bytes!("../tests/fixtures/boot.art");
+        assert_eq!(boot_image_checksum(image).unwrap(), "i;9/7a3c61b5");
+    }
+
+    #[test]
+    fn test_unreadable_header_is_not_a_rejection() {
+        let dir = std::env::temp_dir().join(format!("dexter-verify-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("oat/arm64")).unwrap();
+        std::fs::copy(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/app.apk"), dir.join("base.apk")).unwrap();
+        std::fs::write(dir.join("oat/arm64/base.vdex"), b"vdex099\0").unwrap();
+
+        let pkg = Package { name: "com.example.app".into(), path: dir.join("base.apk").display().to_string() };
+        let info = DexOptInfo {
+            raw_line: String::new(),
+            isa: "arm64".into(),
+            status: "verify".into(),
+            reason: None,
+            primary_abi: true,
+            dex_path: None,
+            location: None,
+        };
+        let result = check(&pkg, &info, None).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(!result.is_rejected());
+        assert!(result.is_unverified());
+        assert!(result.unverified[0].contains("unsupported vdex version"));
+    }
+
+    #[test]
+    fn test_apk_dex_checksums() {
+        // classes3.dex is missing, so classes4.dex is not loaded either
+        let apk = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/app.apk");
+        assert_eq!(apk_dex_checksums(&apk).unwrap(), [0x5f15_e331, 0x9ce9_7e67]);
+        assert!(central_directory(b"PK\x03\x04").is_err());
+    }
+}
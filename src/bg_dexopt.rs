@@ -0,0 +1,492 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::process::Command;
+use std::time::Duration;
+
+/// Job services that run background dexopt (ART Service on Android 14+, PackageManager before).
+pub const JOB_SERVICES: &[&str] = &["BackgroundDexoptJobService", "BackgroundDexOptService"];
+
+/// Background dexopt skips work at or above `THERMAL_STATUS_MODERATE`.
+const THERMAL_CUTOFF: u32 = 2;
+
+/// Artifacts written closer together than this are counted as the same job run.
+const RUN_GAP: Duration = Duration::from_secs(30 * 60);
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Running,
+    Pending,
+    Scheduled,
+}
+
+/// The background dexopt job as registered with the job scheduler.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScheduledJob {
+    pub service: String,
+    pub state: JobState,
+    pub required_constraints: Vec<String>,
+    pub unsatisfied_constraints: Vec<String>,
+    pub enqueue_time: Option<String>,
+    pub last_successful_run: Option<String>,
+    pub last_failed_run: Option<String>,
+}
+
+/// A `START`/`STOP` entry from the job scheduler history.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct JobEvent {
+    pub when: String,
+    pub event: String,
+}
+
+/// One run from the job history that ART Service prints in `pm art dump` (`dumpsys package dexopt`).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct JobRun {
+    pub started: Option<String>,
+    pub status: Option<String>,
+    pub packages: Option<usize>,
+    pub duration_ms: Option<u64>,
+}
+
+impl JobRun {
+    /// Unix time at which the run finished, if its start time can be read.
+    ///
+    /// ART Service prints start times either as epoch milliseconds or in the device's local
+    /// time, which is converted with `utc_offset_secs`.
+    pub fn finished_at(&self, utc_offset_secs: i64) -> Option<u64> {
+        let started = self.started.as_deref()?.trim();
+        let start_secs = match started.parse::<u64>() {
+            Ok(ms) => ms / 1000,
+            Err(_) => {
+                let local = ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"]
+                    .iter()
+                    .find_map(|format| chrono::NaiveDateTime::parse_from_str(started, format).ok())?;
+                u64::try_from(local.and_utc().timestamp() - utc_offset_secs).ok()?
+            }
+        };
+        Some(start_secs + self.duration_ms.unwrap_or(0) / 1000)
+    }
+}
+
+/// The device's offset from UTC in seconds, from `date +%z`; 0 when it cannot be read.
+pub fn utc_offset_secs() -> i64 {
+    Command::new("date")
+        .arg("+%z")
+        .output()
+        .ok()
+        .and_then(|o| parse_utc_offset(&String::from_utf8_lossy(&o.stdout)))
+        .unwrap_or(0)
+}
+
+/// Parses `+0200` / `-0530` into seconds.
+fn parse_utc_offset(raw: &str) -> Option<i64> {
+    let raw = raw.trim();
+    let sign = match raw.get(..1)? {
+        "+" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+    let digits = raw.get(1..5).filter(|d| d.bytes().all(|b| b.is_ascii_digit()))?;
+    let hours: i64 = digits[..2].parse().ok()?;
+    let minutes: i64 = digits[2..].parse().ok()?;
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+/// A guess at the most recent run from the mtimes of `bg-dexopt` artifacts, for devices whose
+/// dump has no job history. Packages that were processed but left unchanged are not counted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ArtifactRun {
+    pub packages: usize,
+    pub finished_secs_ago: u64,
+    pub duration_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BgDexoptStatus {
+    pub job: Option<ScheduledJob>,
+    pub history: Vec<JobEvent>,
+    pub thermal_status: Option<u32>,
+    /// Fields of the legacy `BackgroundDexOptService` dump (`mLastExecution*`, ...).
+    pub last_execution: BTreeMap<String, String>,
+    /// Runs recorded by ART Service, oldest first.
+    pub runs: Vec<JobRun>,
+    #[serde(rename = "estimated_last_run")]
+    pub last_run: Option<ArtifactRun>,
+}
+
+impl BgDexoptStatus {
+    /// Collects the job state from `dumpsys jobscheduler` and `dumpsys thermalservice`.
+    ///
+    /// `dexopt_dump` is the output of `dumpsys package dexopt`, and `artifact_ages` the ages
+    /// of all artifacts compiled with reason `bg-dexopt`; they are only used when the dump
+    /// carries no job history.
+    pub fn collect(dexopt_dump: &str, artifact_ages: &[Duration]) -> Self {
+        let (job, history) = parse_jobscheduler(&dumpsys("jobscheduler"));
+
+        BgDexoptStatus {
+            job,
+            history,
+            thermal_status: parse_thermal_status(&dumpsys("thermalservice")),
+            last_execution: parse_legacy_state(dexopt_dump),
+            runs: parse_job_runs(dexopt_dump),
+            last_run: estimate_last_run(artifact_ages),
+        }
+    }
+
+    /// The latest run ART Service recorded.
+    pub fn last_recorded_run(&self) -> Option<&JobRun> {
+        self.runs.last()
+    }
+
+    pub fn is_thermal_throttled(&self) -> bool {
+        self.thermal_status.is_some_and(|s| s >= THERMAL_CUTOFF)
+    }
+}
+
+fn dumpsys(service: &str) -> String {
+    Command::new("dumpsys")
+        .arg(service)
+        .output()
+        .map(|o| String::from_utf8_lossy(&o.stdout).into_owned())
+        .unwrap_or_default()
+}
+
+fn is_dexopt_job(line: &str) -> bool {
+    JOB_SERVICES.iter().any(|s| line.contains(s))
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// Extracts the dexopt job registration and its history from `dumpsys jobscheduler`.
+pub fn parse_jobscheduler(raw: &str) -> (Option<ScheduledJob>, Vec<JobEvent>) {
+    let mut job: Option<ScheduledJob> = None;
+    let mut history = Vec::new();
+    let mut section = String::new();
+    let mut job_indent: Option<usize> = None;
+    let mut running = false;
+    let mut pending = false;
+
+    for line in raw.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        let indent = indent_of(line);
+
+        if job_indent.is_some_and(|i| indent <= i) {
+            job_indent = None;
+        }
+
+        if indent <= 2 && trimmed.ends_with(':') && !trimmed.starts_with("JOB #") {
+            section = trimmed.trim_end_matches(':').to_string();
+            continue;
+        }
+
+        if let Some(ref mut job) = job
+            && job_indent.is_some()
+        {
+            let list = |v: &str| v.split_whitespace().map(str::to_string).collect::<Vec<_>>();
+            if let Some(v) = trimmed.strip_prefix("Required constraints:") {
+                job.required_constraints = list(v);
+            } else if let Some(v) = trimmed.strip_prefix("Unsatisfied constraints:") {
+                job.unsatisfied_constraints = list(v);
+            } else if let Some(v) = trimmed.strip_prefix("Enqueue time:") {
+                job.enqueue_time = Some(v.trim().to_string());
+            } else if let Some(v) = trimmed.strip_prefix("Last successful run:") {
+                job.last_successful_run = Some(v.trim().to_string());
+            } else if let Some(v) = trimmed.strip_prefix("Last failed run:") {
+                job.last_failed_run = Some(v.trim().to_string());
+            }
+            continue;
+        }
+
+        if !is_dexopt_job(trimmed) {
+            continue;
+        }
+
+        if section.starts_with("Registered") && trimmed.starts_with("JOB #") && job.is_none() {
+            let service = JOB_SERVICES
+                .iter()
+                .find(|s| trimmed.contains(*s))
+                .map(|s| s.to_string())
+                .unwrap_or_default();
+            job = Some(ScheduledJob {
+                service,
+                state: JobState::Scheduled,
+                required_constraints: Vec::new(),
+                unsatisfied_constraints: Vec::new(),
+                enqueue_time: None,
+                last_successful_run: None,
+                last_failed_run: None,
+            });
+            job_indent = Some(indent);
+        } else if section.starts_with("Active jobs") && !trimmed.contains("inactive since") {
+            // Idle slots still mention the last job they ran
+            running = true;
+        } else if section.starts_with("Pending queue") {
+            pending = true;
+        } else if section.starts_with("Job history") {
+            let mut parts = trimmed.split_whitespace();
+            if let (Some(when), Some(event)) = (parts.next(), parts.next()) {
+                history.push(JobEvent {
+                    when: when.to_string(),
+                    event: event.trim_end_matches(':').to_string(),
+                });
+            }
+        }
+    }
+
+    if let Some(ref mut job) = job {
+        job.state = if running {
+            JobState::Running
+        } else if pending {
+            JobState::Pending
+        } else {
+            JobState::Scheduled
+        };
+    }
+
+    (job, history)
+}
+
+/// Reads `Thermal Status: N` from `dumpsys thermalservice`.
+pub fn parse_thermal_status(raw: &str) -> Option<u32> {
+    raw.lines()
+        .find_map(|l| l.trim().strip_prefix("Thermal Status:"))
+        .and_then(|v| v.trim().parse().ok())
+}
+
+/// Picks up the `BackgroundDexOptService` fields that older releases append to `dumpsys package dexopt`.
+pub fn parse_legacy_state(raw: &str) -> BTreeMap<String, String> {
+    const KEYS: &[&str] = &[
+        "mLastExecutionStatus",
+        "mLastExecutionStartTimeMs",
+        "mLastExecutionDurationMs",
+        "mFinishedPostBootUpdate",
+        "mFailedPackageNamesPrimary",
+        "mLastCancelledPackages",
+    ];
+
+    raw.lines()
+        .filter_map(|l| l.trim().split_once(':'))
+        .filter(|(k, _)| KEYS.contains(k))
+        .map(|(k, v)| (k[1..].to_string(), v.trim().to_string()))
+        .collect()
+}
+
+/// Parses `123`, `123ms` or `12s` into milliseconds.
+fn parse_millis(value: &str) -> Option<u64> {
+    let value = value.trim();
+    if let Some(ms) = value.strip_suffix("ms") {
+        ms.trim().parse().ok()
+    } else if let Some(s) = value.strip_suffix('s') {
+        s.trim().parse::<u64>().ok().map(|s| s * 1000)
+    } else {
+        value.parse().ok()
+    }
+}
+
+/// Reads the background dexopt job history from a `pm art dump` / `dumpsys package dexopt` output.
+///
+/// The section starts at a line naming the dexopt job (e.g. `BackgroundDexoptJob:`) and ends at the
+/// next line indented no deeper. Each run is a block of `key: value` lines; a key seen twice starts
+/// the next run, so both one-run-per-line (`key=value, ...`) and multi-line layouts parse.
+pub fn parse_job_runs(raw: &str) -> Vec<JobRun> {
+    let mut runs = Vec::new();
+    let mut section_indent: Option<usize> = None;
+    let mut current = JobRun::default();
+    let mut seen: Vec<&str> = Vec::new();
+
+    for line in raw.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        let indent = indent_of(line);
+
+        if let Some(section) = section_indent {
+            if indent <= section {
+                break;
+            }
+        } else {
+            let lower = trimmed.to_ascii_lowercase();
+            if lower.trim_end_matches(':').ends_with("dexopt job") || lower.starts_with("backgrounddexoptjob") {
+                section_indent = Some(indent);
+            }
+            continue;
+        }
+
+        for field in trimmed.split([',', ';']) {
+            let Some((key, value)) = field.split_once(['=', ':']) else { continue };
+            let normalized: String = key.chars().filter(|c| c.is_ascii_alphanumeric()).collect::<String>().to_ascii_lowercase();
+            let kind = match normalized.as_str() {
+                "start" | "started" | "starttime" | "starttimems" => "started",
+                "status" | "result" => "status",
+                "packages" | "numpackages" | "packagesprocessed" | "dexoptedpackages" => "packages",
+                "duration" | "durationms" => "duration",
+                _ => continue,
+            };
+            if seen.contains(&kind) {
+                runs.push(std::mem::take(&mut current));
+                seen.clear();
+            }
+            seen.push(kind);
+
+            let value = value.trim();
+            match kind {
+                "started" => current.started = Some(value.to_string()),
+                "status" => current.status = Some(value.to_string()),
+                "packages" => current.packages = value.parse().ok(),
+                _ => current.duration_ms = parse_millis(value),
+            }
+        }
+    }
+
+    if !seen.is_empty() {
+        runs.push(current);
+    }
+    runs
+}
+
+/// Dex files that `dex2oat` is compiling right now, from `ps -A -o args`.
+pub fn running_dex2oat_inputs() -> Vec<String> {
+    Command::new("ps")
+        .arg("-A")
+        .arg("-o")
+        .arg("args")
+        .output()
+        .map(|o| parse_dex2oat_inputs(&String::from_utf8_lossy(&o.stdout)))
+        .unwrap_or_default()
+}
+
+fn parse_dex2oat_inputs(raw: &str) -> Vec<String> {
+    raw.lines()
+        .filter(|l| l.contains("dex2oat"))
+        .flat_map(|l| l.split_whitespace())
+        .filter_map(|arg| arg.strip_prefix("--dex-file=").or_else(|| arg.strip_prefix("--zip-location=")))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Asks the system to stop a running job, trying ART Service first and then the legacy command.
+pub fn cancel_job() -> bool {
+    ["pm art cancel-bg-dexopt-job", "cmd package bg-dexopt-job --cancel"]
+        .iter()
+        .any(|cmd| {
+            Command::new("su")
+                .arg("-c")
+                .arg(cmd)
+                .output()
+                .map(|o| o.status.success())
+                .unwrap_or(false)
+        })
+}
+
+/// Groups artifact ages into runs and describes the most recent one.
+pub fn estimate_last_run(artifact_ages: &[Duration]) -> Option<ArtifactRun> {
+    let mut ages = artifact_ages.to_vec();
+    ages.sort();
+
+    let newest = *ages.first()?;
+    let mut oldest = newest;
+    let mut packages = 1;
+    for age in &ages[1..] {
+        if *age - oldest > RUN_GAP {
+            break;
+        }
+        oldest = *age;
+        packages += 1;
+    }
+
+    Some(ArtifactRun {
+        packages,
+        finished_secs_ago: newest.as_secs(),
+        duration_secs: (oldest - newest).as_secs(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jobscheduler_parsing() {
+        let dump = r#"
+  Registered 2 jobs:
+    JOB #1000/27873780: 3b2c1d0 android/com.android.server.art.BackgroundDexoptJobService
+      u0 tag=*job*/android/com.android.server.art.BackgroundDexoptJobService
+      Required constraints: CHARGING IDLE
+      Unsatisfied constraints: CHARGING
+      Enqueue time: -13h1m
+      Last successful run: 2026-10-17 03:02:01.123
+    JOB #u0a120/5: 1a2b3c4 com.example/.SyncJob
+      Required constraints: CONNECTIVITY
+  Pending queue:
+    Pending #0 from android: JOB #1000/27873780 android/com.android.server.art.BackgroundDexoptJobService
+  Job history:
+       -1d2h3m4s   START: #1000/27873780 android/com.android.server.art.BackgroundDexoptJobService
+       -1d1h3m4s    STOP: #1000/27873780 android/com.android.server.art.BackgroundDexoptJobService
+       -5m   START: #u0a120/5 com.example/.SyncJob
+"#;
+        let (job, history) = parse_jobscheduler(dump);
+        let job = job.unwrap();
+        assert_eq!(job.service, "BackgroundDexoptJobService");
+        assert_eq!(job.state, JobState::Pending);
+        assert_eq!(job.required_constraints, ["CHARGING", "IDLE"]);
+        assert_eq!(job.unsatisfied_constraints, ["CHARGING"]);
+        assert_eq!(job.last_successful_run.as_deref(), Some("2026-10-17 03:02:01.123"));
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1], JobEvent { when: "-1d1h3m4s".into(), event: "STOP".into() });
+    }
+
+    #[test]
+    fn test_job_run_parsing() {
+        let dump = r#"
+Dexopt state:
+  [com.example.app]
+    path: /data/app/~~a==/com.example.app-b==/base.apk
+      arm64: [status=speed-profile] [reason=bg-dexopt]
+BackgroundDexoptJob:
+  Job history:
+    Start time: 2026-10-16 03:00:12, Status: JOB_FINISHED, Packages: 41, Duration: 95200ms
+    Start time: 2026-10-17 03:01:40
+    Status: CANCELLED_BY_THERMAL
+    Packages: 7
+    Duration: 12s
+Current GC: CollectorTypeCMC
+"#;
+        let runs = parse_job_runs(dump);
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].packages, Some(41));
+        assert_eq!(runs[0].duration_ms, Some(95200));
+        assert_eq!(
+            runs[1],
+            JobRun {
+                started: Some("2026-10-17 03:01:40".into()),
+                status: Some("CANCELLED_BY_THERMAL".into()),
+                packages: Some(7),
+                duration_ms: Some(12000),
+            }
+        );
+        assert!(parse_job_runs("Dexopt state:\n  [com.a]\n").is_empty());
+
+        // 2026-10-17 03:01:40 at UTC+02:00, plus 12s
+        assert_eq!(runs[1].finished_at(2 * 3600), Some(1_792_198_912));
+        let epoch = JobRun { started: Some("1792198900000".into()), ..runs[1].clone() };
+        assert_eq!(epoch.finished_at(0), Some(1_792_198_912));
+        assert_eq!(parse_utc_offset("-0530\n"), Some(-(5 * 3600 + 30 * 60)));
+        assert_eq!(parse_utc_offset("UTC"), None);
+    }
+
+    #[test]
+    fn test_estimate_last_run() {
+        let mins = |m: u64| Duration::from_secs(m * 60);
+        let run = estimate_last_run(&[mins(50), mins(10), mins(25), mins(600)]).unwrap();
+        assert_eq!(run.packages, 3);
+        assert_eq!(run.finished_secs_ago, 600);
+        assert_eq!(run.duration_secs, 2400);
+        assert!(estimate_last_run(&[]).is_none());
+    }
+}
@@ -0,0 +1,93 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::fmt;
+
+/// ART compiler filters, ordered from least to most optimized.
+///
+/// Dexopt statuses that are not filters (`run-from-apk`, `error`, ...) do not parse and rank below all of them.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, ValueEnum, Deserialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum CompilerFilter {
+    AssumeVerified,
+    Extract,
+    Verify,
+    Quicken,
+    SpaceProfile,
+    Space,
+    SpeedProfile,
+    Speed,
+    EverythingProfile,
+    Everything,
+}
+
+impl CompilerFilter {
+    pub const ALL: [CompilerFilter; 10] = [
+        CompilerFilter::AssumeVerified,
+        CompilerFilter::Extract,
+        CompilerFilter::Verify,
+        CompilerFilter::Quicken,
+        CompilerFilter::SpaceProfile,
+        CompilerFilter::Space,
+        CompilerFilter::SpeedProfile,
+        CompilerFilter::Speed,
+        CompilerFilter::EverythingProfile,
+        CompilerFilter::Everything,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CompilerFilter::AssumeVerified => "assume-verified",
+            CompilerFilter::Extract => "extract",
+            CompilerFilter::Verify => "verify",
+            CompilerFilter::Quicken => "quicken",
+            CompilerFilter::SpaceProfile => "space-profile",
+            CompilerFilter::Space => "space",
+            CompilerFilter::SpeedProfile => "speed-profile",
+            CompilerFilter::Speed => "speed",
+            CompilerFilter::EverythingProfile => "everything-profile",
+            CompilerFilter::Everything => "everything",
+        }
+    }
+
+    /// The filter named by a dexopt status, if it is one.
+    pub fn parse(status: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|f| f.as_str() == status)
+    }
+
+    /// Comma-separated list of every filter, for error messages.
+    pub fn names() -> String {
+        Self::ALL.map(Self::as_str).join(", ")
+    }
+}
+
+impl fmt::Display for CompilerFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Sort key for a dexopt status: non-filter statuses first (alphabetically), then filters by optimization level.
+pub fn status_order(status: &str) -> (Option<CompilerFilter>, &str) {
+    (CompilerFilter::parse(status), status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ordering_and_names() {
+        assert!(CompilerFilter::Verify < CompilerFilter::SpeedProfile);
+        assert!(CompilerFilter::SpaceProfile < CompilerFilter::Space);
+        assert_eq!(CompilerFilter::parse("everything-profile"), Some(CompilerFilter::EverythingProfile));
+        assert_eq!(CompilerFilter::parse("run-from-apk"), None);
+        // The clap names must agree with the dump's spelling
+        for filter in CompilerFilter::ALL {
+            assert_eq!(filter.to_possible_value().unwrap().get_name(), filter.as_str());
+        }
+
+        let mut statuses = vec!["speed", "error", "verify", "run-from-apk", "speed-profile"];
+        statuses.sort_by_key(|s| status_order(s));
+        assert_eq!(statuses, ["error", "run-from-apk", "verify", "speed-profile", "speed"]);
+    }
+}
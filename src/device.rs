@@ -0,0 +1,148 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::process::Command;
+
+/// Snapshot of the Android system properties (`getprop`).
+#[derive(Debug, Clone, Default)]
+pub struct DeviceProps {
+    props: BTreeMap<String, String>,
+}
+
+impl DeviceProps {
+    /// Reads all system properties using `getprop`.
+    pub fn fetch() -> Result<Self> {
+        let output = Command::new("getprop")
+            .output()
+            .with_context(|| "Failed to execute 'getprop' command")?;
+
+        Ok(Self::parse(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// Parses `getprop` output (`[key]: [value]` per line).
+    pub fn parse(raw: &str) -> Self {
+        let mut props = BTreeMap::new();
+
+        for line in raw.lines() {
+            if let Some((key, value)) = line.trim().split_once("]: [")
+                && let Some(key) = key.strip_prefix('[')
+                && let Some(value) = value.strip_suffix(']')
+            {
+                props.insert(key.to_string(), value.to_string());
+            }
+        }
+
+        DeviceProps { props }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.props.get(key).map(String::as_str).filter(|v| !v.is_empty())
+    }
+
+    /// Compiler filter the device uses for a given compilation reason (`pm.dexopt.<reason>`).
+    pub fn compiler_filter_for(&self, reason: &str) -> Option<&str> {
+        self.get(&format!("pm.dexopt.{}", reason))
+    }
+
+    pub fn is_bg_dexopt_disabled(&self) -> bool {
+        self.get("pm.dexopt.disable_bg_dexopt") == Some("true")
+    }
+
+    /// All non-empty properties under `prefix`, keyed by the remainder of the name.
+    pub fn with_prefix(&self, prefix: &str) -> BTreeMap<String, String> {
+        self.props
+            .range(prefix.to_string()..)
+            .take_while(|(k, _)| k.starts_with(prefix))
+            .filter(|(_, v)| !v.is_empty())
+            .map(|(k, v)| (k[prefix.len()..].to_string(), v.clone()))
+            .collect()
+    }
+}
+
+/// The ART mainline module installed on the device.
+#[derive(Debug, Clone, Serialize)]
+pub struct ArtModule {
+    pub package: String,
+    pub version_code: String,
+}
+
+impl ArtModule {
+    /// Looks up the ART APEX using `pm list packages --apex-only --show-versioncode`.
+    pub fn fetch() -> Option<Self> {
+        let output = Command::new("pm")
+            .arg("list")
+            .arg("packages")
+            .arg("--apex-only")
+            .arg("--show-versioncode")
+            .output()
+            .ok()?;
+
+        Self::parse(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    fn parse(raw: &str) -> Option<Self> {
+        raw.lines().find_map(|line| {
+            let p = line.trim().strip_prefix("package:")?;
+            let (package, version) = p.split_once(" versionCode:")?;
+            let package = package.trim();
+            (package == "com.android.art" || package == "com.google.android.art").then(|| ArtModule {
+                package: package.to_string(),
+                version_code: version.trim().to_string(),
+            })
+        })
+    }
+}
+
+/// Dexopt-relevant device configuration, as shown by `dexter device`.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceReport {
+    pub model: Option<String>,
+    pub sdk: Option<String>,
+    pub release: Option<String>,
+    pub abi_list: Option<String>,
+    pub art_module: Option<ArtModule>,
+    /// `pm.dexopt.*`: compiler filter per install/compilation reason.
+    pub pm_dexopt: BTreeMap<String, String>,
+    /// `dalvik.vm.dex2oat-*`: compiler resource limits and defaults.
+    pub dex2oat: BTreeMap<String, String>,
+    /// `dalvik.vm.isa.*`: instruction set variants and features.
+    pub isa: BTreeMap<String, String>,
+}
+
+impl DeviceReport {
+    pub fn collect(props: &DeviceProps) -> Self {
+        let get = |key: &str| props.get(key).map(str::to_string);
+        DeviceReport {
+            model: get("ro.product.model"),
+            sdk: get("ro.build.version.sdk"),
+            release: get("ro.build.version.release"),
+            abi_list: get("ro.product.cpu.abilist"),
+            art_module: ArtModule::fetch(),
+            pm_dexopt: props.with_prefix("pm.dexopt."),
+            dex2oat: props.with_prefix("dalvik.vm.dex2oat-"),
+            isa: props.with_prefix("dalvik.vm.isa."),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_props_and_art_module_parsing() {
+        let props = DeviceProps::parse(
+            "[dalvik.vm.dex2oat-threads]: [4]\n[pm.dexopt.bg-dexopt]: [speed-profile]\n[pm.dexopt.install]: [speed-profile]\n[pm.dexopt.shared]: []\n[ro.build.version.sdk]: [34]\n",
+        );
+        assert_eq!(props.get("ro.build.version.sdk"), Some("34"));
+        assert_eq!(props.compiler_filter_for("install"), Some("speed-profile"));
+
+        let filters = props.with_prefix("pm.dexopt.");
+        assert_eq!(filters.len(), 2);
+        assert_eq!(filters["bg-dexopt"], "speed-profile");
+
+        let art = ArtModule::parse("package:com.android.tzdata versionCode:1\npackage:com.google.android.art versionCode:341111000\n").unwrap();
+        assert_eq!(art.package, "com.google.android.art");
+        assert_eq!(art.version_code, "341111000");
+    }
+}
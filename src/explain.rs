@@ -0,0 +1,266 @@
+use crate::device::DeviceProps;
+use crate::{ApkFlags, ProfileState};
+use serde::Serialize;
+use std::time::Duration;
+
+/// Statuses that get a diagnosis in `--explain` mode.
+pub const EXPLAINED_STATUSES: &[&str] = &["verify", "run-from-apk", "error"];
+
+/// Artifacts older than this are considered neglected by background dexopt.
+const STALE_AFTER: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Problem,
+}
+
+/// A single cause with an optional suggested fix.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    pub severity: Severity,
+    pub cause: String,
+    pub remedy: Option<String>,
+}
+
+impl Finding {
+    fn new(severity: Severity, cause: impl Into<String>, remedy: Option<String>) -> Self {
+        Finding {
+            severity,
+            cause: cause.into(),
+            remedy,
+        }
+    }
+}
+
+/// Everything known about one dexopt entry of a package.
+pub struct Facts<'a> {
+    pub package: &'a str,
+    pub status: &'a str,
+    pub reason: Option<&'a str>,
+    pub flags: Option<&'a ApkFlags>,
+    pub profile: &'a ProfileState,
+    pub installer: Option<&'a str>,
+    pub last_dexopt_age: Option<Duration>,
+    pub device: &'a DeviceProps,
+}
+
+fn is_install_reason(reason: &str) -> bool {
+    reason.starts_with("install")
+}
+
+fn is_boot_reason(reason: &str) -> bool {
+    matches!(
+        reason,
+        "first-boot" | "boot-after-ota" | "boot-after-mainline-update" | "ab-ota"
+    )
+}
+
+/// Produces the findings for a single dexopt entry, most severe first.
+pub fn diagnose(facts: &Facts) -> Vec<Finding> {
+    if !EXPLAINED_STATUSES.contains(&facts.status) {
+        return Vec::new();
+    }
+
+    let mut findings = Vec::new();
+    let optimize_hint = Some(format!("Run `dexter -o {}` to compile it with 'speed'.", facts.package));
+
+    if let Some(flags) = facts.flags {
+        if !flags.has_code {
+            findings.push(Finding::new(
+                Severity::Info,
+                "The APK declares android:hasCode=\"false\"; there is no dex code to compile.",
+                None,
+            ));
+            return findings;
+        }
+        if flags.debuggable {
+            findings.push(Finding::new(
+                Severity::Warning,
+                "The app is debuggable, so ART limits it to 'verify' to keep it debuggable.",
+                Some("Install a release (non-debuggable) build to get AOT-compiled code.".to_string()),
+            ));
+        }
+        if flags.vm_safe_mode {
+            findings.push(Finding::new(
+                Severity::Warning,
+                "The app sets android:vmSafeMode=\"true\", which restricts compilation to 'verify'.",
+                Some("Remove vmSafeMode from the manifest unless the app really needs it.".to_string()),
+            ));
+        }
+    }
+
+    match facts.status {
+        "error" => findings.push(Finding::new(
+            Severity::Problem,
+            "dex2oat failed the last time this package was compiled.",
+            Some(format!(
+                "Check `logcat -s dex2oat artd` for the failure, then retry with `dexter -o {}`.",
+                facts.package
+            )),
+        )),
+        "run-from-apk" => match facts.reason {
+            None => findings.push(Finding::new(
+                Severity::Problem,
+                "No compiled artifacts exist; the package was never dexopted or its artifacts were deleted.",
+                optimize_hint.clone(),
+            )),
+            Some(reason) => findings.push(Finding::new(
+                Severity::Warning,
+                format!("Artifacts from '{}' are unusable, so the app runs from the APK.", reason),
+                optimize_hint.clone(),
+            )),
+        },
+        _ => match facts.reason {
+            Some(reason) if is_install_reason(reason) && !facts.profile.any() => {
+                findings.push(Finding::new(
+                    Severity::Info,
+                    "Installed without a cloud profile, so the install-time filter was 'verify'.",
+                    Some("Use the app for a while; background dexopt compiles it with 'speed-profile' once a profile exists.".to_string()),
+                ));
+            }
+            Some(reason) if is_boot_reason(reason) => findings.push(Finding::new(
+                Severity::Info,
+                format!("Compiled with a fast filter during '{}' to keep boot time low.", reason),
+                Some("Leave the device idle and charging so background dexopt can upgrade it, or run `dexter -o all`.".to_string()),
+            )),
+            Some("inactive") => findings.push(Finding::new(
+                Severity::Info,
+                "The app has not been used for a long time, so background dexopt downgraded it to save space.",
+                None,
+            )),
+            Some("bg-dexopt") if !facts.profile.any() => findings.push(Finding::new(
+                Severity::Info,
+                "Background dexopt found no profile, so there was nothing to compile beyond 'verify'.",
+                Some("Launch and use the app to collect a profile.".to_string()),
+            )),
+            Some("cmdline") => findings.push(Finding::new(
+                Severity::Info,
+                "The package was compiled manually with 'verify' from the command line.",
+                optimize_hint.clone(),
+            )),
+            None => findings.push(Finding::new(
+                Severity::Warning,
+                "No compilation reason was reported for this entry.",
+                optimize_hint.clone(),
+            )),
+            _ => {}
+        },
+    }
+
+    if let Some(reason) = facts.reason
+        && let Some(filter) = facts.device.compiler_filter_for(reason)
+        && (filter == facts.status || filter == "skip")
+    {
+        findings.push(Finding::new(
+            Severity::Info,
+            format!("This device maps reason '{}' to '{}' (pm.dexopt.{}).", reason, filter, reason),
+            None,
+        ));
+    }
+
+    if facts.status == "verify"
+        && facts.reason.is_some_and(is_install_reason)
+        && facts.installer.is_none_or(|i| i != "com.android.vending")
+    {
+        findings.push(Finding::new(
+            Severity::Info,
+            format!(
+                "Installed by '{}'; cloud profiles are only delivered to Play Store installs.",
+                facts.installer.unwrap_or("unknown")
+            ),
+            None,
+        ));
+    }
+
+    if facts.status != "error"
+        && let Some(age) = facts.last_dexopt_age
+        && age > STALE_AFTER
+    {
+        findings.push(Finding::new(
+            Severity::Warning,
+            format!(
+                "Last compiled {} days ago; background dexopt does not seem to run (it needs the device idle and charging).",
+                age.as_secs() / 86_400
+            ),
+            Some("Run `dexter -o all` to trigger the background dexopt job.".to_string()),
+        ));
+    }
+
+    if facts.device.is_bg_dexopt_disabled() {
+        findings.push(Finding::new(
+            Severity::Warning,
+            "Background dexopt is disabled on this device (pm.dexopt.disable_bg_dexopt=true).",
+            Some("Run `setprop pm.dexopt.disable_bg_dexopt false` to re-enable it.".to_string()),
+        ));
+    }
+
+    findings.sort_by_key(|f| std::cmp::Reverse(f.severity));
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn facts<'a>(status: &'a str, reason: Option<&'a str>, device: &'a DeviceProps, profile: &'a ProfileState) -> Facts<'a> {
+        Facts {
+            package: "com.example.app",
+            status,
+            reason,
+            flags: None,
+            profile,
+            installer: Some("com.android.vending"),
+            last_dexopt_age: None,
+            device,
+        }
+    }
+
+    #[test]
+    fn test_fully_compiled_is_not_explained() {
+        let device = DeviceProps::default();
+        let profile = ProfileState::default();
+        assert!(diagnose(&facts("speed-profile", Some("bg-dexopt"), &device, &profile)).is_empty());
+    }
+
+    #[test]
+    fn test_debuggable_and_missing_code() {
+        let device = DeviceProps::default();
+        let profile = ProfileState::default();
+
+        let debuggable = ApkFlags { debuggable: true, vm_safe_mode: false, has_code: true };
+        let mut f = facts("verify", Some("install"), &device, &profile);
+        f.flags = Some(&debuggable);
+        assert!(diagnose(&f).iter().any(|d| d.cause.contains("debuggable")));
+
+        let no_code = ApkFlags { debuggable: false, vm_safe_mode: false, has_code: false };
+        f.flags = Some(&no_code);
+        let findings = diagnose(&f);
+        assert_eq!(findings.len(), 1);
+        assert!(findings[0].cause.contains("hasCode"));
+    }
+
+    #[test]
+    fn test_device_props_and_severity_order() {
+        let device = DeviceProps::parse("[pm.dexopt.install]: [verify]\n[pm.dexopt.disable_bg_dexopt]: [true]\n");
+        let profile = ProfileState::default();
+        let mut f = facts("verify", Some("install"), &device, &profile);
+        f.installer = None;
+
+        let findings = diagnose(&f);
+        assert_eq!(findings[0].severity, Severity::Warning);
+        assert!(findings.iter().any(|d| d.cause.contains("pm.dexopt.install")));
+        assert!(findings.iter().any(|d| d.cause.contains("Installed by 'unknown'")));
+    }
+
+    #[test]
+    fn test_never_compiled() {
+        let device = DeviceProps::default();
+        let profile = ProfileState::default();
+        let findings = diagnose(&facts("run-from-apk", None, &device, &profile));
+        assert_eq!(findings[0].severity, Severity::Problem);
+        assert!(findings[0].remedy.as_deref().unwrap().contains("dexter -o com.example.app"));
+    }
+}
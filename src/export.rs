@@ -0,0 +1,177 @@
+use crate::origin::Origin;
+use crate::{DexOptInfo, Package};
+use std::borrow::Cow;
+use std::io::{self, Write};
+
+/// Column names of the CSV/TSV export. Consumers rely on these, so only ever append.
+pub const COLUMNS: &[&str] = &["package", "label", "path", "isa", "status", "reason", "abi", "split", "origin", "installer"];
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Delimiter {
+    Comma,
+    Tab,
+}
+
+/// Writes delimiter-separated records: RFC 4180 quoting for CSV, backslash escapes for TSV.
+pub struct DelimitedWriter {
+    delimiter: Delimiter,
+}
+
+impl DelimitedWriter {
+    pub fn new(delimiter: Delimiter) -> Self {
+        DelimitedWriter { delimiter }
+    }
+
+    pub fn write_header(&self, out: &mut impl Write) -> io::Result<()> {
+        self.write_record(out, COLUMNS)
+    }
+
+    pub fn write_record<S: AsRef<str>>(&self, out: &mut impl Write, fields: &[S]) -> io::Result<()> {
+        let separator = match self.delimiter {
+            Delimiter::Comma => ",",
+            Delimiter::Tab => "\t",
+        };
+        let line = fields
+            .iter()
+            .map(|f| escape(f.as_ref(), self.delimiter))
+            .collect::<Vec<_>>()
+            .join(separator);
+        writeln!(out, "{}", line)
+    }
+
+    /// Writes one record per dex file and ISA, or a single record with empty dexopt columns.
+    ///
+    /// `path` is the dex file the record describes, falling back to the package's base APK.
+    pub fn write_package(
+        &self,
+        out: &mut impl Write,
+        pkg: &Package,
+        label: Option<&str>,
+        infos: Option<&Vec<DexOptInfo>>,
+        origin: Origin,
+        installer: Option<&str>,
+    ) -> io::Result<()> {
+        for record in package_records(pkg, label, infos, origin, installer) {
+            self.write_record(out, &record)?;
+        }
+        Ok(())
+    }
+}
+
+fn escape(field: &str, delimiter: Delimiter) -> Cow<'_, str> {
+    match delimiter {
+        Delimiter::Comma => {
+            if field.contains([',', '"', '\n', '\r']) {
+                Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+            } else {
+                Cow::Borrowed(field)
+            }
+        }
+        Delimiter::Tab => {
+            if field.contains(['\\', '\t', '\n', '\r']) {
+                Cow::Owned(
+                    field
+                        .replace('\\', "\\\\")
+                        .replace('\t', "\\t")
+                        .replace('\n', "\\n")
+                        .replace('\r', "\\r"),
+                )
+            } else {
+                Cow::Borrowed(field)
+            }
+        }
+    }
+}
+
+/// Value of one `COLUMNS` entry; `info` is `None` for packages without dexopt entries.
+fn field(column: &str, pkg: &Package, label: Option<&str>, info: Option<&DexOptInfo>, origin: Origin, installer: Option<&str>) -> String {
+    match column {
+        "package" => pkg.name.clone(),
+        "label" => label.unwrap_or_default().to_string(),
+        "path" => info.and_then(|i| i.dex_path.clone()).unwrap_or_else(|| pkg.path.clone()),
+        "isa" => info.map(|i| i.isa.clone()).unwrap_or_default(),
+        "status" => info.map(|i| i.status.clone()).unwrap_or_default(),
+        "reason" => info.and_then(|i| i.reason.clone()).unwrap_or_default(),
+        "abi" => info.map(|i| if i.primary_abi { "primary" } else { "secondary" }).unwrap_or_default().to_string(),
+        "split" => info.and_then(DexOptInfo::split_name).unwrap_or_default(),
+        "origin" => origin.to_string(),
+        "installer" => installer.unwrap_or_default().to_string(),
+        _ => unreachable!("no value for export column '{}'", column),
+    }
+}
+
+/// One record per dex file and ISA, with fields in `COLUMNS` order.
+fn package_records(
+    pkg: &Package,
+    label: Option<&str>,
+    infos: Option<&Vec<DexOptInfo>>,
+    origin: Origin,
+    installer: Option<&str>,
+) -> Vec<Vec<String>> {
+    let record = |info: Option<&DexOptInfo>| {
+        COLUMNS
+            .iter()
+            .map(|column| field(column, pkg, label, info, origin, installer))
+            .collect()
+    };
+
+    match infos {
+        Some(infos) if !infos.is_empty() => infos.iter().map(|info| record(Some(info))).collect(),
+        _ => vec![record(None)],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escaping() {
+        let mut out = Vec::new();
+        let csv = DelimitedWriter::new(Delimiter::Comma);
+        csv.write_record(&mut out, &["plain", "a,b", "say \"hi\"", "two\nlines"]).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "plain,\"a,b\",\"say \"\"hi\"\"\",\"two\nlines\"\n");
+
+        let mut out = Vec::new();
+        let tsv = DelimitedWriter::new(Delimiter::Tab);
+        tsv.write_record(&mut out, &["a\tb", "c\\d", "e,f"]).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "a\\tb\tc\\\\d\te,f\n");
+    }
+
+    #[test]
+    fn test_package_records_follow_columns() {
+        let pkg = Package { name: "com.example.app".into(), path: "/data/app/a/base.apk".into() };
+        let infos = vec![DexOptInfo {
+            raw_line: String::new(),
+            isa: "arm64".into(),
+            status: "speed-profile".into(),
+            reason: Some("bg-dexopt".into()),
+            primary_abi: true,
+            dex_path: Some("/data/app/a/split_config.arm64_v8a.apk".into()),
+            location: None,
+        }];
+
+        let records = package_records(&pkg, Some("Example"), Some(&infos), Origin::Data, Some("com.android.vending"));
+        let record: Vec<(&str, &str)> = COLUMNS.iter().copied().zip(records[0].iter().map(String::as_str)).collect();
+        assert_eq!(
+            record,
+            [
+                ("package", "com.example.app"),
+                ("label", "Example"),
+                ("path", "/data/app/a/split_config.arm64_v8a.apk"),
+                ("isa", "arm64"),
+                ("status", "speed-profile"),
+                ("reason", "bg-dexopt"),
+                ("abi", "primary"),
+                ("split", "config.arm64_v8a"),
+                ("origin", "data"),
+                ("installer", "com.android.vending"),
+            ]
+        );
+
+        let records = package_records(&pkg, None, None, Origin::Data, None);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].len(), COLUMNS.len());
+        assert_eq!(records[0][2], "/data/app/a/base.apk");
+    }
+}
@@ -0,0 +1,119 @@
+use crate::compiler_filter::CompilerFilter;
+use crate::{DexOptInfo, Package};
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Whether a package's artifacts still match its APK.
+#[derive(Copy, Clone, PartialEq, Eq, Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Freshness {
+    Fresh,
+    /// An artifact is older than the APK it was compiled from.
+    Stale,
+    /// The dump reports a compiler filter, but the artifacts next to an APK in `/data` are gone.
+    Missing,
+}
+
+impl Freshness {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Freshness::Fresh => "fresh",
+            Freshness::Stale => "stale",
+            Freshness::Missing => "missing",
+        }
+    }
+
+    /// Stale and missing artifacts both mean the app runs slower than its status suggests.
+    pub fn is_outdated(self) -> bool {
+        self != Freshness::Fresh
+    }
+}
+
+impl fmt::Display for Freshness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).ok()?.modified().ok()
+}
+
+/// When and how well a package's artifacts were last produced.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Compilation {
+    /// Newest artifact mtime, as the dump carries no timestamps.
+    pub last_dexopt: Option<SystemTime>,
+    pub freshness: Freshness,
+}
+
+impl Compilation {
+    pub fn check(pkg: &Package, infos: &[DexOptInfo]) -> Self {
+        let mut last_dexopt = None;
+        let mut freshness = Freshness::Fresh;
+
+        for info in infos {
+            let compiled = CompilerFilter::parse(&info.status).is_some();
+            let Some(artifact) = info.artifact_modified(pkg) else {
+                // Preinstalled APKs may keep their artifacts in places the dump does not name
+                if compiled && pkg.path.starts_with("/data/") {
+                    freshness = Freshness::Missing;
+                }
+                continue;
+            };
+            last_dexopt = last_dexopt.max(Some(artifact));
+
+            let apk = modified(Path::new(info.dex_path.as_deref().unwrap_or(&pkg.path)));
+            if compiled && freshness == Freshness::Fresh && apk.is_some_and(|apk| artifact < apk) {
+                freshness = Freshness::Stale;
+            }
+        }
+
+        Compilation { last_dexopt, freshness }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::time::Duration;
+
+    fn info(apk: &Path, isa: &str) -> DexOptInfo {
+        DexOptInfo {
+            raw_line: String::new(),
+            isa: isa.into(),
+            status: "speed-profile".into(),
+            reason: Some("bg-dexopt".into()),
+            primary_abi: isa == "arm64",
+            dex_path: Some(apk.to_string_lossy().into_owned()),
+            location: None,
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let dir = std::env::temp_dir().join(format!("dexter-freshness-{}", std::process::id()));
+        fs::create_dir_all(dir.join("oat/arm64")).unwrap();
+        let apk = dir.join("base.apk");
+        let odex = dir.join("oat/arm64/base.odex");
+        let now = SystemTime::now();
+        File::create(&apk).unwrap().set_modified(now).unwrap();
+        File::create(&odex).unwrap().set_modified(now + Duration::from_secs(60)).unwrap();
+
+        // Not under /data, so the missing arm artifact is not reported
+        let pkg = Package { name: "com.a".into(), path: apk.to_string_lossy().into_owned() };
+        let infos = [info(&apk, "arm64"), info(&apk, "arm")];
+        let compilation = Compilation::check(&pkg, &infos);
+        assert_eq!(compilation.freshness, Freshness::Fresh);
+        assert_eq!(compilation.last_dexopt, Some(now + Duration::from_secs(60)));
+
+        File::options().write(true).open(&apk).unwrap().set_modified(now + Duration::from_secs(120)).unwrap();
+        assert_eq!(Compilation::check(&pkg, &infos).freshness, Freshness::Stale);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,200 @@
+use crate::UI;
+use crate::report::Report;
+use colored::Color;
+use std::fmt::Write;
+
+const STYLE: &str = r#"
+body { font-family: system-ui, sans-serif; margin: 2rem; color: #212121; }
+h1 { margin-bottom: 0.2rem; }
+.meta { color: #616161; margin-bottom: 1.5rem; }
+.chart text { font-size: 13px; }
+.controls { margin: 1.5rem 0 0.5rem; display: flex; gap: 0.5rem; }
+.controls input { flex: 1; max-width: 24rem; padding: 0.3rem; }
+table { border-collapse: collapse; width: 100%; font-size: 14px; }
+th, td { text-align: left; padding: 0.3rem 0.6rem; border-bottom: 1px solid #e0e0e0; }
+th { cursor: pointer; user-select: none; background: #f5f5f5; position: sticky; top: 0; }
+th.asc::after { content: " \25B2"; }
+th.desc::after { content: " \25BC"; }
+td.num { text-align: right; font-variant-numeric: tabular-nums; }
+.status { font-weight: 600; }
+"#;
+
+const SCRIPT: &str = r#"
+const table = document.getElementById('packages');
+const rows = Array.from(table.tBodies[0].rows);
+const search = document.getElementById('search');
+const status = document.getElementById('status');
+function applyFilter() {
+  const q = search.value.toLowerCase();
+  for (const row of rows) {
+    const text = row.dataset.search;
+    const ok = (!q || text.includes(q)) && (!status.value || row.dataset.status === status.value);
+    row.hidden = !ok;
+  }
+}
+search.addEventListener('input', applyFilter);
+status.addEventListener('change', applyFilter);
+table.tHead.querySelectorAll('th').forEach((th, col) => {
+  th.addEventListener('click', () => {
+    const desc = th.classList.contains('asc');
+    table.tHead.querySelectorAll('th').forEach(h => h.classList.remove('asc', 'desc'));
+    th.classList.add(desc ? 'desc' : 'asc');
+    const key = r => r.cells[col].dataset.sort ?? r.cells[col].textContent;
+    const numeric = th.dataset.type === 'number';
+    rows.sort((a, b) => {
+      const x = key(a), y = key(b);
+      const c = numeric ? Number(x) - Number(y) : x.localeCompare(y);
+      return desc ? -c : c;
+    });
+    rows.forEach(r => table.tBodies[0].appendChild(r));
+  });
+});
+"#;
+
+/// CSS equivalent of the terminal colors from `UI::get_status_color` (theme colors included).
+fn css_color(status: &str) -> &'static str {
+    match UI::get_status_color(status) {
+        Color::Green | Color::BrightGreen => "#2e7d32",
+        Color::Yellow | Color::BrightYellow => "#f9a825",
+        Color::Blue | Color::BrightBlue => "#1565c0",
+        Color::Red | Color::BrightRed => "#c62828",
+        Color::Magenta | Color::BrightMagenta => "#ad1457",
+        Color::Cyan | Color::BrightCyan => "#00838f",
+        // White is unreadable on a white page
+        _ => "#757575",
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Horizontal bar chart of the per-status counts as inline SVG.
+fn render_chart(html: &mut String, report: &Report) {
+    let statuses = &report.summary.statuses;
+    let max = statuses.values().copied().max().unwrap_or(0).max(1);
+    let row_height = 26;
+    let height = statuses.len() * row_height + 10;
+
+    let _ = writeln!(html, r#"<svg class="chart" width="640" height="{}" role="img" aria-label="Status summary">"#, height);
+    for (i, (status, count)) in statuses.iter().enumerate() {
+        let y = i * row_height + 5;
+        let width = (*count as f64 / max as f64 * 420.0).max(2.0);
+        let _ = writeln!(
+            html,
+            r#"<text x="130" y="{}" text-anchor="end">{}</text><rect x="140" y="{}" width="{:.0}" height="18" fill="{}"/><text x="{:.0}" y="{}">{}</text>"#,
+            y + 14,
+            escape(status),
+            y,
+            width,
+            css_color(status),
+            140.0 + width + 6.0,
+            y + 14,
+            count
+        );
+    }
+    html.push_str("</svg>\n");
+}
+
+/// Renders the report as a single HTML page with inline CSS, SVG and JavaScript.
+pub fn render(report: &Report) -> String {
+    let mut html = String::new();
+    let meta = &report.metadata;
+
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str("<title>Dexopt report</title>\n<style>");
+    html.push_str(STYLE);
+    html.push_str("</style>\n</head>\n<body>\n<h1>Dexopt report</h1>\n");
+
+    let device = match (&meta.device_model, &meta.sdk) {
+        (Some(model), Some(sdk)) => format!("{} (SDK {}) &middot; ", escape(model), escape(sdk)),
+        (Some(model), None) => format!("{} &middot; ", escape(model)),
+        _ => String::new(),
+    };
+    let _ = writeln!(
+        html,
+        r#"<div class="meta">{} apps &middot; {} packages &middot; {}{} &middot; dexter {}</div>"#,
+        escape(&meta.app_type),
+        report.summary.total_apps,
+        device,
+        escape(&meta.generated_at),
+        meta.tool_version
+    );
+
+    html.push_str("<h2>Summary</h2>\n");
+    render_chart(&mut html, report);
+
+    html.push_str("<h2>Packages</h2>\n<div class=\"controls\">\n");
+    html.push_str("<input id=\"search\" type=\"search\" placeholder=\"Filter by package, label or reason\">\n");
+    html.push_str("<select id=\"status\"><option value=\"\">All statuses</option>");
+    for status in report.summary.statuses.keys() {
+        let _ = write!(html, "<option>{}</option>", escape(status));
+    }
+    html.push_str("</select>\n</div>\n");
+
+    html.push_str("<table id=\"packages\">\n<thead><tr><th>Package</th><th>Label</th><th>ISA</th><th>Status</th><th>Reason</th><th data-type=\"number\">Artifact size</th></tr></thead>\n<tbody>\n");
+    for pkg in report.packages() {
+        let label = pkg.label.as_deref().unwrap_or_default();
+        for entry in &pkg.dexopt {
+            let reason = entry.reason.unwrap_or_default();
+            let bytes = entry.artifact_bytes.unwrap_or(0);
+            let _ = writeln!(
+                html,
+                r#"<tr data-status="{status}" data-search="{search}" title="{raw}"><td>{name}</td><td>{label}</td><td>{isa}</td><td class="status" style="color:{color}">{status}</td><td>{reason}</td><td class="num" data-sort="{bytes}">{size}</td></tr>"#,
+                status = escape(entry.status),
+                search = escape(&format!("{} {} {}", pkg.package, label, reason).to_lowercase()),
+                raw = escape(entry.raw),
+                name = escape(pkg.package),
+                label = escape(label),
+                isa = escape(entry.isa),
+                color = css_color(entry.status),
+                reason = escape(reason),
+                bytes = bytes,
+                size = entry.artifact_bytes.map(format_bytes).unwrap_or_default(),
+            );
+        }
+    }
+    html.push_str("</tbody>\n</table>\n<script>");
+    html.push_str(SCRIPT);
+    html.push_str("</script>\n</body>\n</html>\n");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_and_sizes() {
+        assert_eq!(escape("<a href=\"x\">&</a>"), "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;");
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(3 * 1024 * 1024 / 2), "1.5 MiB");
+        assert_eq!(css_color("speed"), "#2e7d32");
+    }
+}
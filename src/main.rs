@@ -1,13 +1,55 @@
+mod artifact;
+mod bg_dexopt;
+mod compiler_filter;
+mod device;
+mod explain;
+mod export;
+mod freshness;
+mod html;
+mod markdown;
+mod metrics;
+mod origin;
+mod policy;
+mod query;
+mod reason;
+mod report;
+mod serve;
+mod sorting;
+mod sqlite;
+mod table;
+mod theme;
+mod tui;
+
 use apk_info::Apk;
 use anyhow::{Context, Result};
-use clap::{Parser, ValueEnum};
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
 use colored::*;use rayon::prelude::*;use regex::Regex;
 use std::collections::{BTreeMap, HashMap};
 use std::fmt;
-use std::io::{self, Write};
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::os::unix::process::CommandExt;
 use std::process::{Command, Stdio};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use artifact::ArtifactCheck;
+use bg_dexopt::BgDexoptStatus;
+use compiler_filter::CompilerFilter;
+use device::{DeviceProps, DeviceReport};
+use explain::{Facts, Finding, Severity};
+use origin::{Origin, Origins};
+use policy::{Policy, Violation};
+use export::{DelimitedWriter, Delimiter};
+use sorting::{GroupBy, SortKey};
+use table::Column;
+use theme::{ColorChoice, Theme};
+use freshness::{Compilation, Freshness};
+use report::{Listing, PackageRecord, Record, Report, RunMetadata, Summary, SummaryRecord};
 use serde::Serialize;
-use serde_json::json;
 use once_cell::sync::Lazy;
 use unicode_width::UnicodeWidthStr;
 use terminal_size::{Width, terminal_size};
@@ -16,29 +58,157 @@ use terminal_size::{Width, terminal_size};
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
     /// Filter packages by name (substring match)
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     filter: Option<String>,
 
     /// Filter by specific dexopt status (e.g., 'speed', 'verify', 'error')
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     status: Option<String>,
 
+    /// Only show packages with an entry compiled at least this well
+    #[arg(long, value_enum, value_name = "FILTER", global = true)]
+    min_status: Option<CompilerFilter>,
+
+    /// Only show packages with an entry compiled at most this well
+    #[arg(long, value_enum, value_name = "FILTER", global = true)]
+    max_status: Option<CompilerFilter>,
+
+    /// Only show packages from these origins (comma-separated)
+    #[arg(long, value_enum, value_name = "ORIGIN", value_delimiter = ',', global = true)]
+    origin: Vec<Origin>,
+
+    /// Only show packages installed by this package ('none' for preinstalled or sideloaded apps)
+    #[arg(long, value_name = "PACKAGE", global = true)]
+    installer: Option<String>,
+
+    /// Only show packages whose artifacts are older than their APK, or missing after an update
+    #[arg(long, global = true)]
+    stale: bool,
+
+    /// Filter with an expression, e.g. "status == verify and (size > 10M or installer like 'com.android.*')"
+    #[arg(long = "where", value_name = "EXPR", global = true)]
+    r#where: Option<String>,
+
+    /// When to use colors (auto honours NO_COLOR / CLICOLOR_FORCE and disables colors when stdout is not a terminal)
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = ColorChoice::Auto, global = true)]
+    color: ColorChoice,
+
+    /// Draw boxes, bars and markers with plain ASCII characters
+    #[arg(long, global = true)]
+    ascii: bool,
+
+    /// Status colors to use instead of the built-in ones (default: ~/.config/dexter/theme.toml, if present)
+    #[arg(long, value_name = "FILE", global = true)]
+    theme: Option<PathBuf>,
+
     /// Type of applications to analyze
-    #[arg(short, long, value_enum, default_value_t = AppType::User)]
+    #[arg(short, long, value_enum, default_value_t = AppType::User, global = true)]
     r#type: AppType,
 
-    /// Show detailed information for each package
+    /// Show detailed information for each package (with --format markdown: raw dexopt lines)
     #[arg(short, long)]
     verbose: bool,
 
     /// Output results as JSON
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     json: bool,
 
+    /// Machine-readable output format (`-j` is short for `--format json`)
+    #[arg(long, value_enum, global = true)]
+    format: Option<OutputFormat>,
+
+    /// Order of the package listing (size and install-time list the largest / newest first)
+    #[arg(long, value_enum, value_name = "KEY", default_value_t = SortKey::Name)]
+    sort: SortKey,
+
+    /// Group the table view and JSON output, with a header and count per group
+    #[arg(long, value_enum, value_name = "KEY")]
+    group_by: Option<GroupBy>,
+
+    /// Columns of the table view (comma-separated); status and reason split per ISA on multi-ABI devices
+    #[arg(long, value_enum, value_name = "COLUMN", value_delimiter = ',', default_values_t = table::DEFAULT_COLUMNS.to_vec())]
+    columns: Vec<Column>,
+
+    /// Append this run (packages, dexopt entries, APK metadata) to the SQLite database FILE
+    #[arg(long, value_name = "FILE")]
+    export_sqlite: Option<PathBuf>,
+
+    /// Check the results against a TOML/YAML policy and exit non-zero on violations
+    #[arg(long, value_name = "FILE")]
+    policy: Option<PathBuf>,
+
+    /// Write the --json / --format output to FILE instead of stdout (long form only; -o is --optimize)
+    #[arg(long, value_name = "FILE", global = true)]
+    output: Option<PathBuf>,
+
     /// Optimize application(s). Use 'all' for background dexopt job, or specify a package name.
     #[arg(short = 'o', long = "optimize")]
     optimize: Option<String>,
+
+    /// Diagnose why packages are at 'verify', 'run-from-apk' or 'error' and suggest fixes
+    #[arg(short = 'e', long)]
+    explain: bool,
+
+    /// Refresh the summary and package list every SECONDS (default 5), highlighting status changes
+    #[arg(
+        short = 'w',
+        long,
+        value_name = "SECONDS",
+        num_args = 0..=1,
+        default_missing_value = "5",
+        conflicts_with_all = ["json", "format"]
+    )]
+    watch: Option<u64>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Show the device's dexopt configuration alongside the analysis summary
+    Device,
+    /// Show when background dexopt last ran, whether it is pending and what blocks it
+    BgDexopt,
+    /// Browse packages interactively: search, sort, inspect, optimize and reset
+    Tui,
+    /// Print the JSON Schema of the JSON and NDJSON output
+    Schema,
+    /// Read the vdex/oat headers and report artifacts ART would reject (changed APK, updated boot image)
+    Verify,
+    /// Serve a local JSON API and/or Prometheus metrics over HTTP
+    #[command(group(ArgGroup::new("addr").required(true).multiple(true).args(["listen", "metrics_addr"])))]
+    Serve {
+        /// Address for the JSON API: /packages, /packages/{name}, /summary, POST /packages/{name}/optimize
+        #[arg(long, value_name = "ADDR")]
+        listen: Option<String>,
+        /// Address to expose `/metrics` on (e.g. 0.0.0.0:9464)
+        #[arg(long, value_name = "ADDR")]
+        metrics_addr: Option<String>,
+    },
+}
+
+impl Args {
+    fn output_format(&self) -> Option<OutputFormat> {
+        if self.json { Some(OutputFormat::Json) } else { self.format }
+    }
+
+    /// Whether stdout carries machine-readable output, so progress messages must stay off it.
+    fn is_machine_output(&self) -> bool {
+        self.output_format().is_some()
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+enum OutputFormat {
+    Json,
+    Ndjson,
+    Csv,
+    Tsv,
+    Html,
+    Markdown,
+    Prometheus,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
@@ -46,6 +216,10 @@ enum AppType {
     User,
     System,
     All,
+    Apex,
+    Enabled,
+    Disabled,
+    Uninstalled,
 }
 
 impl fmt::Display for AppType {
@@ -54,6 +228,10 @@ impl fmt::Display for AppType {
             AppType::User => "User",
             AppType::System => "System",
             AppType::All => "All",
+            AppType::Apex => "APEX",
+            AppType::Enabled => "Enabled",
+            AppType::Disabled => "Disabled",
+            AppType::Uninstalled => "Uninstalled",
         };
         write!(f, "{}", name)
     }
@@ -65,57 +243,136 @@ struct Package {
     path: String,
 }
 
+/// Manifest flags that change how ART compiles an app.
+#[derive(Debug, Clone, Serialize)]
+struct ApkFlags {
+    debuggable: bool,
+    vm_safe_mode: bool,
+    has_code: bool,
+}
+
+impl ApkFlags {
+    fn from_apk(apk: &Apk) -> Self {
+        let flag = |name: &str, default: bool| {
+            apk.get_attribute_value("application", name)
+                .map_or(default, |v| v == "true")
+        };
+
+        ApkFlags {
+            debuggable: flag("debuggable", false),
+            vm_safe_mode: flag("vmSafeMode", false),
+            has_code: flag("hasCode", true),
+        }
+    }
+}
+
+/// Manifest metadata shown in the TUI detail pane.
+#[derive(Debug, Clone, Serialize)]
+struct ApkDetails {
+    version_name: Option<String>,
+    version_code: Option<String>,
+    min_sdk: Option<String>,
+    target_sdk: u32,
+    flags: ApkFlags,
+}
+
+/// Which profiles are available to profile-guided compilation.
+#[derive(Debug, Clone, Default, Serialize)]
+struct ProfileState {
+    current: bool,
+    reference: bool,
+    dex_metadata: bool,
+}
+
+impl ProfileState {
+    fn any(&self) -> bool {
+        self.current || self.reference || self.dex_metadata
+    }
+}
+
+fn is_non_empty_file(path: impl AsRef<Path>) -> bool {
+    fs::metadata(path).map(|m| m.is_file() && m.len() > 0).unwrap_or(false)
+}
+
 impl Package {
     /// Fetches the package list using `pm list packages`.
     fn fetch_list(app_type: AppType) -> Result<Vec<Self>> {
-        let filter_flag = match app_type {
-            AppType::User => "-3",
-            AppType::System => "-s",
-            AppType::All => "",
+        let mut list = match app_type {
+            AppType::User => Self::list_packages(&["-3"])?,
+            AppType::System => Self::list_packages(&["-s"])?,
+            AppType::All => Self::list_packages(&[])?,
+            AppType::Enabled => Self::list_packages(&["-e"])?,
+            AppType::Disabled => Self::list_packages(&["-d"])?,
+            AppType::Uninstalled => {
+                // `-u` adds packages uninstalled for the user but still on the system image
+                let installed: HashSet<String> = Self::list_packages(&[])?.into_iter().map(|p| p.name).collect();
+                Self::list_packages(&["-u"])?
+                    .into_iter()
+                    .filter(|p| !installed.contains(&p.name))
+                    .collect()
+            }
+            AppType::Apex => {
+                // The modules themselves, plus the APKs they ship
+                let mut list = Self::list_packages(&["--apex-only"])?;
+                list.extend(Self::list_packages(&["-s"])?.into_iter().filter(|p| p.path.starts_with("/apex/")));
+                list
+            }
         };
 
-        // Performance: Execute `pm` directly instead of `sh -c`
-        let mut cmd = Command::new("pm");
-        cmd.arg("list").arg("packages").arg("-f");
-        if !filter_flag.is_empty() {
-            cmd.arg(filter_flag);
-        }
+        list.sort_by(|a, b| a.name.cmp(&b.name));
+        list.dedup_by(|a, b| a.name == b.name);
+        Ok(list)
+    }
 
-        let output = cmd.output()
+    /// Runs `pm list packages -f` with extra flags.
+    fn list_packages(flags: &[&str]) -> Result<Vec<Self>> {
+        // Performance: Execute `pm` directly instead of `sh -c`
+        let output = Command::new("pm")
+            .arg("list")
+            .arg("packages")
+            .arg("-f")
+            .args(flags)
+            .output()
             .with_context(|| "Failed to execute 'pm' command")?;
 
         let raw = String::from_utf8_lossy(&output.stdout);
         let mut list = Vec::new();
 
         for line in raw.lines() {
-            if let Some(p) = line.trim().strip_prefix("package:") {
-                if let Some((path, name)) = p.rsplit_once('=') {
-                    list.push(Package {
-                        name: name.trim().to_string(),
-                        path: path.trim().to_string(),
-                    });
-                }
+            let Some(p) = line.trim().strip_prefix("package:") else {
+                continue;
+            };
+            if let Some((path, name)) = p.rsplit_once('=') {
+                list.push(Package {
+                    name: name.trim().to_string(),
+                    path: path.trim().to_string(),
+                });
+            } else if let Some(name) = p.split_whitespace().next() {
+                // Older releases print APEX modules without a path
+                list.push(Package {
+                    name: name.to_string(),
+                    path: format!("/apex/{}", name),
+                });
             }
         }
 
-        list.sort_by(|a, b| a.name.cmp(&b.name));
         Ok(list)
     }
 
     /// Gets the application label from the APK file.
     fn get_label(&self) -> Option<String> {
         // 1. Try native parsing (Fast)
-        if let Ok(apk) = Apk::new(&self.path) {
-            if let Some(label) = apk.get_application_label() {
-                let clean = label.trim().replace(['\r', '\n'], " ");
-                if !clean.is_empty() {
-                    // Heuristic: Filter out internal class names
-                    let is_class_name = clean.contains('.') && !clean.contains(' ') && clean != self.name;
-                    let looks_like_class = clean.chars().all(|c: char| c.is_alphanumeric() || c == '.' || c == '_');
-                    
-                    if !is_class_name || !looks_like_class {
-                         return Some(clean);
-                    }
+        if let Ok(apk) = Apk::new(&self.path)
+            && let Some(label) = apk.get_application_label()
+        {
+            let clean = label.trim().replace(['\r', '\n'], " ");
+            if !clean.is_empty() {
+                // Heuristic: Filter out internal class names
+                let is_class_name = clean.contains('.') && !clean.contains(' ') && clean != self.name;
+                let looks_like_class = clean.chars().all(|c: char| c.is_alphanumeric() || c == '.' || c == '_');
+
+                if !is_class_name || !looks_like_class {
+                     return Some(clean);
                 }
             }
         }
@@ -139,15 +396,69 @@ impl Package {
         let stdout = String::from_utf8_lossy(&output.stdout);
         for line in stdout.lines() {
             let trimmed = line.trim();
-            if let Some(label) = trimmed.strip_prefix("application-label:'") {
-                if let Some(end) = label.find('‘') {
-                    return Some(label[..end].to_string());
-                }
+            if let Some(label) = trimmed.strip_prefix("application-label:'")
+                && let Some(end) = label.find('‘')
+            {
+                return Some(label[..end].to_string());
             }
         }
         None
     }
 
+    /// Reads the `<application>` flags relevant to dexopt from the APK manifest.
+    fn get_apk_flags(&self) -> Option<ApkFlags> {
+        let apk = Apk::new(&self.path).ok()?;
+        Some(ApkFlags::from_apk(&apk))
+    }
+
+    /// Reads version, SDK levels and dexopt flags from the APK manifest.
+    fn get_apk_details(&self) -> Option<ApkDetails> {
+        let apk = Apk::new(&self.path).ok()?;
+        Some(ApkDetails {
+            version_name: apk.get_version_name(),
+            version_code: apk.get_version_code(),
+            min_sdk: apk.get_min_sdk_version(),
+            target_sdk: apk.get_target_sdk_version(),
+            flags: ApkFlags::from_apk(&apk),
+        })
+    }
+
+    /// Checks for current/reference profiles and a dex metadata (`.dm`) file next to the APK.
+    fn get_profile_state(&self) -> ProfileState {
+        let dm_path = Path::new(&self.path).with_extension("dm");
+        ProfileState {
+            current: is_non_empty_file(format!("/data/misc/profiles/cur/0/{}/primary.prof", self.name)),
+            reference: is_non_empty_file(format!("/data/misc/profiles/ref/{}/primary.prof", self.name)),
+            dex_metadata: is_non_empty_file(dm_path),
+        }
+    }
+
+    /// Fetches the installer of every package using `pm list packages -i`.
+    fn fetch_installers() -> Result<HashMap<String, String>> {
+        let output = Command::new("pm")
+            .arg("list")
+            .arg("packages")
+            .arg("-i")
+            .output()
+            .with_context(|| "Failed to execute 'pm' command")?;
+
+        let raw = String::from_utf8_lossy(&output.stdout);
+        let mut installers = HashMap::new();
+
+        for line in raw.lines() {
+            if let Some(p) = line.trim().strip_prefix("package:")
+                && let Some((name, installer)) = p.split_once("installer=")
+            {
+                let installer = installer.trim();
+                if !installer.is_empty() && installer != "null" {
+                    installers.insert(name.trim().to_string(), installer.to_string());
+                }
+            }
+        }
+
+        Ok(installers)
+    }
+
     fn is_aapt_available() -> bool {
         Command::new("which")
             .arg("aapt")
@@ -162,15 +473,74 @@ impl Package {
 #[derive(Debug, Clone, Serialize)]
 struct DexOptInfo {
     raw_line: String,
+    isa: String,
     status: String,
+    reason: Option<String>,
+    primary_abi: bool,
+    /// The dex container (base or split APK) this entry belongs to.
+    dex_path: Option<String>,
+    /// Artifact path reported by ART (`[location is ...]`), if any.
+    location: Option<String>,
+}
+
+impl DexOptInfo {
+    /// `base` for the base APK, otherwise the split name (`split_config.arm64_v8a.apk` -> `config.arm64_v8a`).
+    fn split_name(&self) -> Option<String> {
+        let stem = Path::new(self.dex_path.as_ref()?).file_stem()?.to_str()?;
+        Some(stem.strip_prefix("split_").unwrap_or(stem).to_string())
+    }
+
+    /// Path of the compiled artifact, falling back to the usual `oat/<isa>/` layout next to the APK.
+    fn artifact_path(&self, pkg: &Package) -> Option<PathBuf> {
+        if let Some(ref location) = self.location {
+            return Some(PathBuf::from(location));
+        }
+        let apk = Path::new(self.dex_path.as_deref().unwrap_or(&pkg.path));
+        let stem = apk.file_stem()?;
+        let mut path = apk.parent()?.join("oat").join(&self.isa).join(stem);
+        path.set_extension("odex");
+        Some(path)
+    }
+
+    /// Total size of the `.odex`, `.vdex` and `.art` files, if any of them exists.
+    fn artifact_bytes(&self, pkg: &Package) -> Option<u64> {
+        let odex = self.artifact_path(pkg)?;
+        ["odex", "vdex", "art"]
+            .iter()
+            .filter_map(|ext| fs::metadata(odex.with_extension(ext)).ok())
+            .map(|m| m.len())
+            .reduce(|a, b| a + b)
+    }
+
+    /// When the `.odex` (or, for filters without compiled code, the `.vdex`) was last written.
+    fn artifact_modified(&self, pkg: &Package) -> Option<SystemTime> {
+        let odex = self.artifact_path(pkg)?;
+        ["odex", "vdex"]
+            .iter()
+            .find_map(|ext| fs::metadata(odex.with_extension(ext)).ok()?.modified().ok())
+    }
+
+    /// Time since the artifact was last written, if it exists.
+    fn last_dexopt_age(&self, pkg: &Package) -> Option<Duration> {
+        SystemTime::now().duration_since(self.artifact_modified(pkg)?).ok()
+    }
 }
 
 struct Analyzer {
     results: HashMap<String, Vec<DexOptInfo>>,
 }
 
+/// A package whose dexopt status differs between two dumps.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct StatusChange {
+    package: String,
+    before: Option<String>,
+    after: Option<String>,
+}
+
 static STATUS_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(arm64:|arm:)").expect("Invalid regex for status"));
 static FILTER_EXTRACT_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\b(?:status|filter)=([^]\s]+)").expect("Invalid regex for filter extraction"));
+static REASON_EXTRACT_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\breason=([^]\s]+)").expect("Invalid regex for reason extraction"));
 
 impl Analyzer {
     /// Fetches the dexopt dump from `dumpsys package dexopt`.
@@ -188,6 +558,7 @@ impl Analyzer {
     fn new(dump: &str) -> Self {
         let mut results: HashMap<String, Vec<DexOptInfo>> = HashMap::new();
         let mut current_pkg: Option<String> = None;
+        let mut current_dex: Option<String> = None;
 
         for line in dump.lines() {
             let trimmed = line.trim();
@@ -201,19 +572,39 @@ impl Analyzer {
                 && !trimmed.contains('=')
             {
                 current_pkg = Some(trimmed[1..trimmed.len() - 1].to_string());
-            } else if let Some(ref pkg) = current_pkg {
-                if STATUS_RE.is_match(trimmed) {
-                    let status = FILTER_EXTRACT_RE
-                        .captures(trimmed)
-                        .and_then(|c| c.get(1))
-                        .map(|m| m.as_str().to_string())
-                        .unwrap_or_else(|| "unknown".to_string());
-
-                    results.entry(pkg.clone()).or_default().push(DexOptInfo {
-                        raw_line: trimmed.to_string(),
-                        status,
-                    });
-                }
+                current_dex = None;
+            } else if let Some(path) = trimmed.strip_prefix("path:") {
+                current_dex = Some(path.trim().to_string());
+            } else if let Some(ref pkg) = current_pkg
+                && STATUS_RE.is_match(trimmed)
+            {
+                let status = FILTER_EXTRACT_RE
+                    .captures(trimmed)
+                    .and_then(|c| c.get(1))
+                    .map(|m| m.as_str().to_string())
+                    .unwrap_or_else(|| "unknown".to_string());
+                let reason = REASON_EXTRACT_RE
+                    .captures(trimmed)
+                    .and_then(|c| c.get(1))
+                    .map(|m| m.as_str().to_string());
+                let isa = trimmed.split(':').next().unwrap_or_default().to_string();
+
+                results.entry(pkg.clone()).or_default().push(DexOptInfo {
+                    raw_line: trimmed.to_string(),
+                    isa,
+                    status,
+                    reason,
+                    primary_abi: trimmed.contains("[primary-abi]"),
+                    dex_path: current_dex.clone(),
+                    location: None,
+                });
+            } else if let Some(ref pkg) = current_pkg
+                && let Some(location) = trimmed
+                    .strip_prefix("[location is ")
+                    .and_then(|l| l.strip_suffix(']'))
+                && let Some(last) = results.get_mut(pkg).and_then(|infos| infos.last_mut())
+            {
+                last.location = Some(location.to_string());
             }
         }
 
@@ -223,19 +614,54 @@ impl Analyzer {
     fn get_info(&self, pkg_name: &str) -> Option<&Vec<DexOptInfo>> {
         self.results.get(pkg_name)
     }
+
+    /// Distinct statuses of a package joined with '/', e.g. `speed-profile/verify` for mixed ISAs.
+    fn status_of(&self, pkg_name: &str) -> Option<String> {
+        let mut statuses: Vec<&str> = Vec::new();
+        for info in self.get_info(pkg_name)? {
+            if !statuses.contains(&info.status.as_str()) {
+                statuses.push(&info.status);
+            }
+        }
+        Some(statuses.join("/"))
+    }
+
+    /// Lists the packages whose status changed in `newer`, sorted by name.
+    fn diff(&self, newer: &Analyzer) -> Vec<StatusChange> {
+        let mut names: Vec<&String> = self.results.keys().chain(newer.results.keys()).collect();
+        names.sort();
+        names.dedup();
+
+        names
+            .into_iter()
+            .filter_map(|name| {
+                let before = self.status_of(name);
+                let after = newer.status_of(name);
+                (before != after).then(|| StatusChange {
+                    package: name.clone(),
+                    before,
+                    after,
+                })
+            })
+            .collect()
+    }
 }
 
 struct UI;
 
 impl UI {
     fn get_status_color(status: &str) -> Color {
-        match status {
-            "speed-profile" | "speed" => Color::Green,
-            "verify" => Color::Yellow,
-            "quicken" => Color::Blue,
-            "run-from-apk" | "error" => Color::Red,
-            "everything" => Color::Magenta,
-            _ => Color::White,
+        if let Some(color) = theme::status_color(status) {
+            return color;
+        }
+        match CompilerFilter::parse(status) {
+            Some(CompilerFilter::SpeedProfile | CompilerFilter::Speed) => Color::Green,
+            Some(CompilerFilter::Verify) => Color::Yellow,
+            Some(CompilerFilter::Quicken) => Color::Blue,
+            Some(CompilerFilter::Everything) => Color::Magenta,
+            Some(_) => Color::White,
+            None if status == "run-from-apk" || status == "error" => Color::Red,
+            None => Color::White,
         }
     }
 
@@ -248,6 +674,31 @@ impl UI {
         }
     }
 
+    fn print_table_entry(
+        stdout: &mut io::Stdout,
+        pkg: &Package,
+        infos: &[DexOptInfo],
+        highlight: bool,
+    ) -> io::Result<()> {
+        for (i, info) in infos.iter().enumerate() {
+            let colored_raw = Self::colorize_line(&info.raw_line, &info.status);
+            if i == 0 {
+                let name = format!("{:<45}", pkg.name);
+                let name = if highlight { name.black().on_bright_yellow() } else { name.bright_white() };
+                writeln!(stdout, "{} | {}", name, colored_raw)?;
+            } else {
+                writeln!(stdout, "{:<45} | {}", "", colored_raw)?;
+            }
+        }
+        writeln!(stdout)
+    }
+
+    fn print_group_header(stdout: &mut io::Stdout, group: &str, count: usize) -> io::Result<()> {
+        let g = theme::glyphs();
+        let title = format!("{} ({})", group, count);
+        writeln!(stdout, "{} {}\n", g.group.bright_blue(), title.bold().color(Self::get_status_color(group)))
+    }
+
     fn print_header() {
         println!(
             "\n{} | {}\n",
@@ -266,7 +717,14 @@ impl UI {
 
             info_list: Option<&Vec<DexOptInfo>>,
 
+            origin: Origin,
+
+            installer: Option<&str>,
+
+            compilation: Option<Compilation>,
+
         ) -> io::Result<()> {
+            let g = theme::glyphs();
 
             let min_width = 40;
 
@@ -348,11 +806,11 @@ impl UI {
 
             
 
-            let border = "─".repeat(box_width);
+            let border = g.horizontal.repeat(box_width);
 
     
 
-            writeln!(stdout, "{}", format!("┌{}┐", border).cyan())?;
+            writeln!(stdout, "{}", format!("{}{}{}", g.top_left, border, g.top_right).cyan())?;
 
     
 
@@ -406,19 +864,27 @@ impl UI {
 
                 "{}{}{}{}",
 
-                "│".cyan(),
+                g.vertical.cyan(),
 
                 " ".repeat(p_l),
 
                 inner_content,
 
-                format!("{}{}", " ".repeat(p_r), "│").cyan()
+                format!("{}{}", " ".repeat(p_r), g.vertical).cyan()
 
             )?;
 
     
 
-            writeln!(stdout, "{}", format!("└{}┘", border).cyan())?;
+            writeln!(stdout, "{}", format!("{}{}{}", g.bottom_left, border, g.bottom_right).cyan())?;
+            writeln!(stdout, "  {} {} {} {} {}", "origin:".dimmed(), origin, g.separator, "installer:".dimmed(), installer.unwrap_or("none"))?;
+            if let Some(compilation) = compilation {
+                let freshness = match compilation.freshness {
+                    Freshness::Fresh => String::new(),
+                    other => format!(" ({})", other).red().bold().to_string(),
+                };
+                writeln!(stdout, "  {} {}{}", "last dexopt:".dimmed(), Self::format_last_dexopt(compilation.last_dexopt), freshness)?;
+            }
 
     
 
@@ -484,71 +950,1099 @@ impl UI {
 
         }
 
-    fn print_summary(total_apps: usize, stats: &BTreeMap<String, usize>, app_type: AppType) {
+    fn print_summary(summary: &Summary, app_type: AppType) {
+        let g = theme::glyphs();
         let width = 47;
         let b_blue = Color::BrightBlue;
         let b_yellow = Color::BrightYellow;
 
-        println!("\n\n{}", format!("╔{}╗", "═".repeat(width)).color(b_blue));
+        println!("\n\n{}", format!("{}{}{}", g.double_top_left, g.double_horizontal.repeat(width), g.double_top_right).color(b_blue));
         
         let title = "DEXOPT ANALYSIS SUMMARY";
         let p_s = (width - title.len()) / 2;
         let p_e = width - title.len() - p_s;
         println!(
             "{}{}{}{}",
-            "║".color(b_blue),
+            g.double_vertical.color(b_blue),
             " ".repeat(p_s),
             title.bold().color(b_yellow),
-            format!("{}{}", " ".repeat(p_e), "║").color(b_blue)
+            format!("{}{}", " ".repeat(p_e), g.double_vertical).color(b_blue)
         );
 
-        let mid = format!("╠{}╣", "═".repeat(width)).color(b_blue);
+        let mid = format!("{}{}{}", g.double_left_tee, g.double_horizontal.repeat(width), g.double_right_tee).color(b_blue);
         println!("{}", mid);
         
         Self::add_summary_line("App Scope", &app_type.to_string(), Color::Cyan, Color::Magenta, width);
-        Self::add_summary_line("Total Apps Checked", &total_apps.to_string(), Color::Cyan, Color::BrightGreen, width);
+        Self::add_summary_line("Total Apps Checked", &summary.total_apps.to_string(), Color::Cyan, Color::BrightGreen, width);
+        for (label, count) in [("Stale Artifacts", summary.stale), ("Missing Artifacts", summary.missing_artifacts)] {
+            let color = if count > 0 { Color::BrightRed } else { Color::BrightGreen };
+            Self::add_summary_line(label, &count.to_string(), Color::Cyan, color, width);
+        }
         
-        println!("{}", mid);
-        let sub = "Profile Breakdown";
-        let p_s = (width - sub.len()) / 2;
-        let p_e = width - sub.len() - p_s;
-        println!(
-            "{}{}{}{}",
-            "║".color(b_blue),
-            " ".repeat(p_s),
-            sub.dimmed().bold(),
-            format!("{}{}", " ".repeat(p_e), "║").color(b_blue)
-        );
-        println!("{}", mid);
+        Self::print_summary_section("Profile Breakdown", width);
 
+        let stats = &summary.statuses;
         if stats.is_empty() {
             let msg = "No profile data found.";
             let padding = " ".repeat(width.saturating_sub(2 + msg.len()));
-            println!("{}  {}{}{}", "║".color(b_blue), msg, padding, "║".color(b_blue));
+            println!("{}  {}{}{}", g.double_vertical.color(b_blue), msg, padding, g.double_vertical.color(b_blue));
         } else {
+            let mut stats: Vec<(&String, &usize)> = stats.iter().collect();
+            stats.sort_by_key(|(profile, _)| compiler_filter::status_order(profile));
             for (profile, count) in stats {
                 let color = Self::get_status_color(profile);
                 Self::add_summary_line(profile, &count.to_string(), Color::Cyan, color, width);
             }
         }
-        println!("{}", format!("╚{}╝", "═".repeat(width)).color(b_blue));
+
+        for (title, counts) in [
+            ("Reason Breakdown", &summary.reasons),
+            ("Origin Breakdown", &summary.origins),
+            ("Installer Breakdown", &summary.installers),
+        ] {
+            if counts.is_empty() {
+                continue;
+            }
+            Self::print_summary_section(title, width);
+            for (name, count) in counts {
+                Self::add_summary_line(name, &count.to_string(), Color::Cyan, Color::BrightWhite, width);
+            }
+        }
+        println!("{}", format!("{}{}{}", g.double_bottom_left, g.double_horizontal.repeat(width), g.double_bottom_right).color(b_blue));
+
+        Self::print_reason_table(summary);
+    }
+
+    /// Cross-tab of dexopt entries per status and reason, followed by what each reason means.
+    fn print_reason_table(summary: &Summary) {
+        if summary.status_reasons.is_empty() {
+            return;
+        }
+        let reasons: Vec<&String> = summary.reasons.keys().collect();
+        let mut statuses: Vec<&String> = summary.status_reasons.keys().collect();
+        statuses.sort_by_key(|status| compiler_filter::status_order(status));
+        let status_width = statuses.iter().map(|s| s.width()).max().unwrap_or(0).max("Status".len());
+
+        println!("\n{}", "Entries by Status and Reason".bold().underline());
+        let header: Vec<String> = reasons.iter().map(|r| r.bold().to_string()).collect();
+        println!("  {:<status_width$}  {}", "", header.join("  "));
+        for status in statuses {
+            let counts = &summary.status_reasons[status];
+            let cells: Vec<String> = reasons
+                .iter()
+                .map(|reason| {
+                    let cell = format!("{:>width$}", counts.get(*reason).map_or("-".to_string(), usize::to_string), width = reason.width());
+                    if counts.contains_key(*reason) { cell } else { cell.dimmed().to_string() }
+                })
+                .collect();
+            println!(
+                "  {}  {}",
+                format!("{:<status_width$}", status).color(Self::get_status_color(status)),
+                cells.join("  ")
+            );
+        }
+
+        let reason_width = reasons.iter().map(|r| r.width()).max().unwrap_or(0);
+        println!();
+        for name in reasons {
+            let description = reason::describe(name).unwrap_or(match name.as_str() {
+                reason::NONE => "The dump gave no reason for these entries",
+                _ => "Not a reason dexter knows about",
+            });
+            println!("  {}  {}", format!("{:<reason_width$}", name).cyan(), description.dimmed());
+        }
+    }
+
+    fn print_summary_section(title: &str, width: usize) {
+        let g = theme::glyphs();
+        let b_blue = Color::BrightBlue;
+        let mid = format!("{}{}{}", g.double_left_tee, g.double_horizontal.repeat(width), g.double_right_tee).color(b_blue);
+        println!("{}", mid);
+        let p_s = (width - title.len()) / 2;
+        let p_e = width - title.len() - p_s;
+        println!(
+            "{}{}{}{}",
+            g.double_vertical.color(b_blue),
+            " ".repeat(p_s),
+            title.dimmed().bold(),
+            format!("{}{}", " ".repeat(p_e), g.double_vertical).color(b_blue)
+        );
+        println!("{}", mid);
+    }
+
+    fn print_diagnosis(stdout: &mut io::Stdout, findings: &[Finding]) -> io::Result<()> {
+        let g = theme::glyphs();
+        writeln!(stdout, "  {}", "Diagnosis:".bold().underline())?;
+        for finding in findings {
+            let (marker, color) = match finding.severity {
+                Severity::Problem => (g.cross, Color::Red),
+                Severity::Warning => ("!", Color::Yellow),
+                Severity::Info => ("i", Color::Cyan),
+            };
+            writeln!(stdout, "    {} {}", marker.color(color).bold(), finding.cause)?;
+            if let Some(ref remedy) = finding.remedy {
+                writeln!(stdout, "      {} {}", g.arrow.green(), remedy.dimmed())?;
+            }
+        }
+        writeln!(stdout)
+    }
+
+    fn print_artifact_checks(checks: &[ArtifactCheck], boot_images: &BTreeMap<&str, Option<String>>) {
+        let g = theme::glyphs();
+        for (isa, checksum) in boot_images {
+            println!("{} {} {}", "Boot image".dimmed(), isa, checksum.as_deref().unwrap_or("not found"));
+        }
+        let rejected: Vec<&ArtifactCheck> = checks.iter().filter(|c| c.is_rejected()).collect();
+        let unverified: Vec<&ArtifactCheck> = checks.iter().filter(|c| c.is_unverified()).collect();
+        for check in &rejected {
+            println!(
+                "\n  {} {} {} {}",
+                g.cross.red().bold(),
+                check.package.bold(),
+                check.isa,
+                Self::colorize_line(check.status, check.status)
+            );
+            println!("    {}", check.vdex.dimmed());
+            for problem in &check.problems {
+                println!("    {} {}", g.arrow.red(), problem);
+            }
+        }
+        for check in &unverified {
+            println!("\n  {} {} {} {}", "?".yellow().bold(), check.package.bold(), check.isa, Self::colorize_line(check.status, check.status));
+            println!("    {}", check.vdex.dimmed());
+            for reason in &check.unverified {
+                println!("    {} {}", g.arrow.yellow(), reason.dimmed());
+            }
+        }
+
+        println!();
+        let verified = checks.len() - unverified.len();
+        if rejected.is_empty() {
+            println!("{} {}", g.check.green().bold(), format!("All {} verified artifacts match their APK and boot image.", verified).bold());
+        } else {
+            let msg = format!(
+                "{} of {} verified artifacts would be rejected by ART; these apps run from the APK despite their status.",
+                rejected.len(),
+                verified
+            );
+            println!("{}", msg.red().bold());
+        }
+        if !unverified.is_empty() {
+            let msg = format!("{} artifacts could not be verified, as dexter cannot read their headers.", unverified.len());
+            println!("{}", msg.yellow());
+        }
+    }
+
+    fn print_policy_violations(out: &mut dyn Write, violations: &[Violation]) -> io::Result<()> {
+        let g = theme::glyphs();
+        writeln!(out)?;
+        if violations.is_empty() {
+            return writeln!(out, "{} {}", g.check.green().bold(), "Policy satisfied.".bold());
+        }
+        writeln!(out, "{}", format!("Policy violations ({}):", violations.len()).red().bold())?;
+        for violation in violations {
+            writeln!(out, "  {} {} {}", g.cross.red().bold(), violation.package.bold(), violation.message)?;
+        }
+        Ok(())
+    }
+
+    fn print_device_report(report: &DeviceReport) {
+        let unknown = || "unknown".to_string();
+
+        let device_lines = vec![
+            ("Model".to_string(), report.model.clone().unwrap_or_else(unknown)),
+            (
+                "Android".to_string(),
+                format!(
+                    "{} (SDK {})",
+                    report.release.as_deref().unwrap_or("?"),
+                    report.sdk.as_deref().unwrap_or("?")
+                ),
+            ),
+            ("ABIs".to_string(), report.abi_list.clone().unwrap_or_else(unknown)),
+            (
+                "ART Module".to_string(),
+                report
+                    .art_module
+                    .as_ref()
+                    .map(|m| format!("{} ({})", m.package, m.version_code))
+                    .unwrap_or_else(unknown),
+            ),
+        ];
+        let to_lines = |map: &BTreeMap<String, String>| -> Vec<(String, String)> {
+            map.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+        };
+
+        Self::print_report_box(
+            "DEVICE DEXOPT CONFIGURATION",
+            &[
+                ("Device", device_lines),
+                ("Compiler Filters (pm.dexopt.*)", to_lines(&report.pm_dexopt)),
+                ("dex2oat (dalvik.vm.dex2oat-*)", to_lines(&report.dex2oat)),
+                ("ISA (dalvik.vm.isa.*)", to_lines(&report.isa)),
+            ],
+        );
+    }
+
+    fn print_bg_dexopt_status(status: &BgDexoptStatus) {
+        let none = || "none".to_string();
+        let mut job_lines = Vec::new();
+
+        match status.job {
+            Some(ref job) => {
+                job_lines.push(("Service".to_string(), job.service.clone()));
+                job_lines.push(("State".to_string(), format!("{:?}", job.state).to_lowercase()));
+                job_lines.push(("Requires".to_string(), job.required_constraints.join(", ")));
+                let blocked = if job.unsatisfied_constraints.is_empty() {
+                    none()
+                } else {
+                    job.unsatisfied_constraints.join(", ")
+                };
+                job_lines.push(("Waiting For".to_string(), blocked));
+                if let Some(ref t) = job.enqueue_time {
+                    job_lines.push(("Enqueued".to_string(), t.clone()));
+                }
+                if let Some(ref t) = job.last_successful_run {
+                    job_lines.push(("Last Successful Run".to_string(), t.clone()));
+                }
+                if let Some(ref t) = job.last_failed_run {
+                    job_lines.push(("Last Failed Run".to_string(), t.clone()));
+                }
+            }
+            None => job_lines.push(("Service".to_string(), "not registered".to_string())),
+        }
+
+        if let Some(level) = status.thermal_status {
+            let name = ["none", "light", "moderate", "severe", "critical", "emergency", "shutdown"]
+                .get(level as usize)
+                .copied()
+                .unwrap_or("unknown");
+            let throttled = if status.is_thermal_throttled() { ", dexopt paused" } else { "" };
+            job_lines.push(("Thermal Status".to_string(), format!("{} ({}{})", level, name, throttled)));
+        }
+
+        // The artifact-based numbers are only a guess, so they are shown when ART Service recorded no run
+        let run_section = match status.last_recorded_run() {
+            Some(run) => {
+                let unknown = || "unknown".to_string();
+                let lines = vec![
+                    ("Started".to_string(), run.started.clone().unwrap_or_else(unknown)),
+                    ("Status".to_string(), run.status.clone().unwrap_or_else(unknown)),
+                    ("Packages Processed".to_string(), run.packages.map_or_else(unknown, |n| n.to_string())),
+                    ("Duration".to_string(), run.duration_ms.map_or_else(unknown, |ms| Self::format_duration(ms / 1000))),
+                ];
+                ("Last Run (job history)", lines)
+            }
+            None => {
+                let lines = status
+                    .last_run
+                    .as_ref()
+                    .map(|run| {
+                        vec![
+                            ("Packages Compiled".to_string(), format!("~{}", run.packages)),
+                            ("Finished".to_string(), format!("~{} ago", Self::format_duration(run.finished_secs_ago))),
+                            ("Duration".to_string(), format!("~{}", Self::format_duration(run.duration_secs))),
+                        ]
+                    })
+                    .unwrap_or_default();
+                ("Last Run (estimated from artifact mtimes)", lines)
+            }
+        };
+
+        let legacy_lines = status
+            .last_execution
+            .iter()
+            .map(|(k, v)| (k.clone(), if v.is_empty() { none() } else { v.clone() }))
+            .collect();
+
+        // Most recent events last, as in `dumpsys jobscheduler`
+        let history_lines = status
+            .history
+            .iter()
+            .rev()
+            .take(10)
+            .rev()
+            .map(|e| (e.event.clone(), e.when.clone()))
+            .collect();
+
+        Self::print_report_box(
+            "BACKGROUND DEXOPT JOB",
+            &[
+                ("Job Scheduler", job_lines),
+                run_section,
+                ("Last Execution (legacy service)", legacy_lines),
+                ("History", history_lines),
+            ],
+        );
+    }
+
+    /// The job skips packages that are already up to date without touching them, so `total` is an upper bound
+    /// and so is the ETA.
+    fn print_progress(done: usize, total: usize, elapsed: Duration, current: Option<&str>) {
+        let g = theme::glyphs();
+        let bar_width = 30;
+        let ratio = if total == 0 { 0.0 } else { done as f64 / total as f64 };
+        let filled = ((ratio * bar_width as f64) as usize).min(bar_width);
+        let eta = if done == 0 {
+            "--".to_string()
+        } else {
+            let remaining = elapsed.as_secs_f64() / done as f64 * total.saturating_sub(done) as f64;
+            format!("up to {}", Self::format_duration(remaining as u64))
+        };
+
+        let max_width = terminal_size().map_or(120, |(Width(w), _)| w as usize);
+        let mut line = format!(
+            "[{}{}] {} of up to {} packages ({:.0}%), ETA {}",
+            g.bar_full.repeat(filled),
+            g.bar_empty.repeat(bar_width - filled),
+            done,
+            total,
+            ratio * 100.0,
+            eta
+        );
+        if let Some(current) = current {
+            line = format!("{}  {} {}", line, g.arrow, current);
+        }
+        if line.width() >= max_width {
+            line = line.chars().take(max_width.saturating_sub(1)).collect();
+        }
+
+        // Progress goes to stderr so it never mixes with JSON on stdout; logs get one line per update
+        let mut stderr = io::stderr();
+        if theme::cursor_control(stderr.is_terminal()) {
+            let _ = write!(stderr, "\r\x1b[2K{}", line.cyan());
+        } else {
+            let _ = writeln!(stderr, "{}", line.cyan());
+        }
+        let _ = stderr.flush();
+    }
+
+    fn clear_progress() {
+        let mut stderr = io::stderr();
+        if theme::cursor_control(stderr.is_terminal()) {
+            let _ = write!(stderr, "\r\x1b[2K");
+            let _ = stderr.flush();
+        }
+    }
+
+    fn print_status_changes(changes: &[StatusChange]) {
+        let g = theme::glyphs();
+        if changes.is_empty() {
+            println!("{}", "No dexopt status changed.".dimmed());
+            return;
+        }
+
+        println!("\n{}", format!("Status changes ({}):", changes.len()).bold().underline());
+        let name_width = changes.iter().map(|c| c.package.width()).max().unwrap_or(0);
+        for change in changes {
+            let before = change.before.as_deref().unwrap_or("none");
+            let after = change.after.as_deref().unwrap_or("none");
+            println!(
+                "  {} {} {} {}",
+                format!("{:<name_width$}", change.package).bright_white(),
+                Self::colorize_line(before, before),
+                g.arrow.dimmed(),
+                Self::colorize_line(after, after)
+            );
+        }
+    }
+
+    /// Age of the newest artifact, e.g. `3d 4h ago`.
+    fn format_last_dexopt(last_dexopt: Option<SystemTime>) -> String {
+        match last_dexopt.and_then(|t| SystemTime::now().duration_since(t).ok()) {
+            Some(age) => format!("{} ago", Self::format_duration(age.as_secs())),
+            None => "unknown".to_string(),
+        }
+    }
+
+    fn format_duration(secs: u64) -> String {
+        let (d, h, m, s) = (secs / 86_400, secs / 3600 % 24, secs / 60 % 60, secs % 60);
+        match (d, h, m) {
+            (0, 0, 0) => format!("{}s", s),
+            (0, 0, _) => format!("{}m {}s", m, s),
+            (0, _, _) => format!("{}h {}m", h, m),
+            _ => format!("{}d {}h", d, h),
+        }
+    }
+
+    /// Prints titled sections of `label : value` lines in a box sized to fit its content.
+    fn print_report_box(title: &str, sections: &[(&str, Vec<(String, String)>)]) {
+        let g = theme::glyphs();
+        let b_blue = Color::BrightBlue;
+
+        let label_width = sections
+            .iter()
+            .flat_map(|(_, lines)| lines.iter().map(|(l, _)| l.width()))
+            .max()
+            .unwrap_or(0);
+        let value_width = sections
+            .iter()
+            .flat_map(|(_, lines)| lines.iter().map(|(_, v)| v.width()))
+            .max()
+            .unwrap_or(0);
+        let width = (label_width + value_width + 7).max(47);
+
+        let center = |text: ColoredString, len: usize| {
+            let p_s = (width - len) / 2;
+            let p_e = width - len - p_s;
+            println!(
+                "{}{}{}{}",
+                g.double_vertical.color(b_blue),
+                " ".repeat(p_s),
+                text,
+                format!("{}{}", " ".repeat(p_e), g.double_vertical).color(b_blue)
+            );
+        };
+        let mid = format!("{}{}{}", g.double_left_tee, g.double_horizontal.repeat(width), g.double_right_tee).color(b_blue);
+
+        println!("\n{}", format!("{}{}{}", g.double_top_left, g.double_horizontal.repeat(width), g.double_top_right).color(b_blue));
+        center(title.bold().color(Color::BrightYellow), title.len());
+
+        for (name, lines) in sections {
+            println!("{}", mid);
+            center(name.dimmed().bold(), name.len());
+            println!("{}", mid);
+
+            if lines.is_empty() {
+                let msg = "Not available.";
+                let padding = " ".repeat(width.saturating_sub(2 + msg.len()));
+                println!("{}  {}{}{}", g.double_vertical.color(b_blue), msg.dimmed(), padding, g.double_vertical.color(b_blue));
+            }
+            for (label, value) in lines {
+                let padding = " ".repeat(width.saturating_sub(5 + label_width + value.width()));
+                println!(
+                    "{}  {} : {}{}{}",
+                    g.double_vertical.color(b_blue),
+                    format!("{:<label_width$}", label).bold().color(Color::Cyan),
+                    value.bold().color(Self::get_status_color(value)),
+                    padding,
+                    g.double_vertical.color(b_blue)
+                );
+            }
+        }
+        println!("{}", format!("{}{}{}", g.double_bottom_left, g.double_horizontal.repeat(width), g.double_bottom_right).color(b_blue));
     }
 
     fn add_summary_line(label: &str, value: &str, l_col: Color, v_col: Color, width: usize) {
+        let g = theme::glyphs();
+        // Long labels such as installer package names would push the border out
+        let label = if label.chars().count() > 22 {
+            let kept = 22 - g.ellipsis.width();
+            format!("{}{}", label.chars().take(kept).collect::<String>(), g.ellipsis)
+        } else {
+            label.to_string()
+        };
         let l_part = format!("{:<22}", label).bold().color(l_col);
         let v_part = value.bold().color(v_col);
         let padding = " ".repeat(width.saturating_sub(5 + 22 + value.len()));
         println!(
             "{}  {} : {}{}{}",
-            "║".color(Color::BrightBlue),
+            g.double_vertical.color(Color::BrightBlue),
             l_part,
             v_part,
             padding,
-            "║".color(Color::BrightBlue)
+            g.double_vertical.color(Color::BrightBlue)
         );
     }
 }
 
+/// Runs the explain rules over every dexopt entry of a package, dropping repeated findings.
+fn explain_package(
+    pkg: &Package,
+    infos: &[DexOptInfo],
+    flags: Option<&ApkFlags>,
+    installer: Option<&str>,
+    device: &DeviceProps,
+) -> Vec<Finding> {
+    let profile = pkg.get_profile_state();
+    let mut findings: Vec<Finding> = Vec::new();
+
+    for info in infos {
+        let facts = Facts {
+            package: &pkg.name,
+            status: &info.status,
+            reason: info.reason.as_deref(),
+            flags,
+            profile: &profile,
+            installer,
+            last_dexopt_age: info.last_dexopt_age(pkg),
+            device,
+        };
+        for finding in explain::diagnose(&facts) {
+            if !findings.contains(&finding) {
+                findings.push(finding);
+            }
+        }
+    }
+
+    findings.sort_by_key(|f| std::cmp::Reverse(f.severity));
+    findings
+}
+
+/// Set by Ctrl-C while an `InterruptScope` is alive; long-running modes poll it to stop cleanly.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static TRAPPING: AtomicBool = AtomicBool::new(false);
+
+/// Ctrl-C sets `INTERRUPTED` until this is dropped, and exits the process as usual afterwards.
+struct InterruptScope;
+
+impl Drop for InterruptScope {
+    fn drop(&mut self) {
+        TRAPPING.store(false, Ordering::SeqCst);
+        INTERRUPTED.store(false, Ordering::SeqCst);
+    }
+}
+
+fn trap_interrupts() -> Result<InterruptScope> {
+    // A handler cannot be removed once set, so it checks `TRAPPING` instead
+    let handler = || {
+        if TRAPPING.load(Ordering::SeqCst) {
+            INTERRUPTED.store(true, Ordering::SeqCst);
+        } else {
+            std::process::exit(130);
+        }
+    };
+    match ctrlc::set_handler(handler) {
+        Ok(()) | Err(ctrlc::Error::MultipleHandlers) => {}
+        Err(e) => return Err(e).with_context(|| "Failed to install Ctrl-C handler"),
+    }
+    INTERRUPTED.store(false, Ordering::SeqCst);
+    TRAPPING.store(true, Ordering::SeqCst);
+    Ok(InterruptScope)
+}
+
+/// Package name, status and `--where` filters, shared by the listing, watch mode and the TUI search.
+#[derive(Debug, Clone, Default)]
+struct Filters {
+    name: Option<String>,
+    status: Option<String>,
+    min_status: Option<CompilerFilter>,
+    max_status: Option<CompilerFilter>,
+    origin: Vec<Origin>,
+    installer: Option<String>,
+    stale: bool,
+    query: Option<Arc<query::Query>>,
+    /// Fetched only when a filter looks at origins or installers.
+    origins: Option<Arc<Origins>>,
+}
+
+impl Filters {
+    fn from_args(args: &Args) -> Result<Self> {
+        let query = args.r#where.as_deref().map(query::Query::new).transpose()?;
+        let needs_origins = !args.origin.is_empty()
+            || args.installer.is_some()
+            || query.as_ref().is_some_and(query::Query::needs_origins);
+        Ok(Filters {
+            name: args.filter.clone(),
+            status: args.status.clone(),
+            min_status: args.min_status,
+            max_status: args.max_status,
+            origin: args.origin.clone(),
+            installer: args.installer.clone(),
+            stale: args.stale,
+            query: query.map(Arc::new),
+            origins: needs_origins.then(|| Origins::fetch(args.r#type)).transpose()?.map(Arc::new),
+        })
+    }
+
+    /// Fetches origins and installers again, for modes that run longer than one install.
+    fn refresh_origins(&mut self, app_type: AppType) -> Result<()> {
+        if self.origins.is_some() {
+            self.origins = Some(Arc::new(Origins::fetch(app_type)?));
+        }
+        Ok(())
+    }
+
+    /// Origins for display, sorting and grouping, reusing the ones fetched for filtering.
+    fn listing_origins(&self, app_type: AppType) -> Arc<Origins> {
+        match self.origins {
+            Some(ref origins) => Arc::clone(origins),
+            None => Arc::new(Origins::fetch(app_type).unwrap_or_default()),
+        }
+    }
+
+    fn matches_name(&self, name: &str) -> bool {
+        self.name.as_ref().is_none_or(|f| name.contains(f.as_str()))
+    }
+
+    /// Whether one entry has the exact `--status` and lies within `--min-status` / `--max-status`.
+    fn matches_status(&self, info_list: Option<&Vec<DexOptInfo>>) -> bool {
+        if self.status.is_none() && self.min_status.is_none() && self.max_status.is_none() {
+            return true;
+        }
+        // If a status filter is active but the app has no info, skip it
+        info_list.is_some_and(|infos| {
+            infos.iter().any(|i| {
+                let filter = CompilerFilter::parse(&i.status);
+                self.status.as_ref().is_none_or(|s| &i.status == s)
+                    && self.min_status.is_none_or(|min| filter.is_some_and(|f| f >= min))
+                    && self.max_status.is_none_or(|max| filter.is_some_and(|f| f <= max))
+            })
+        })
+    }
+
+    fn matches(&self, pkg: &Package, info_list: Option<&Vec<DexOptInfo>>) -> bool {
+        self.matches_name(&pkg.name)
+            && self.matches_status(info_list)
+            && self.matches_origin(pkg)
+            && (!self.stale || info_list.is_some_and(|infos| Compilation::check(pkg, infos).freshness.is_outdated()))
+            && self.query.as_ref().is_none_or(|q| q.matches(pkg, info_list, self.origins.as_deref()))
+    }
+
+    fn matches_origin(&self, pkg: &Package) -> bool {
+        let Some(ref origins) = self.origins else {
+            return true;
+        };
+        let installer_matches = self.installer.as_deref().is_none_or(|wanted| match origins.installer(pkg) {
+            Some(installer) => installer == wanted,
+            None => wanted == "none",
+        });
+        installer_matches && (self.origin.is_empty() || self.origin.contains(&origins.origin(pkg)))
+    }
+}
+
+/// The text listing: a table, or per-line / block entries, with `--group-by` headers.
+/// Shared by the normal run and watch mode.
+struct TextListing<'a> {
+    args: &'a Args,
+    keys: &'a sorting::Keys<'a>,
+    use_table: bool,
+    group_counts: HashMap<String, usize>,
+    current_group: Option<String>,
+    rows: Vec<table::Row<'a>>,
+}
+
+impl<'a> TextListing<'a> {
+    /// `packages` must already be sorted with `keys`, so that each group is contiguous.
+    fn new(args: &'a Args, keys: &'a sorting::Keys<'a>, use_table: bool, packages: &[&Package]) -> Self {
+        let mut group_counts: HashMap<String, usize> = HashMap::new();
+        if let Some(group_by) = args.group_by {
+            for pkg in packages.iter().filter(|pkg| Self::shown(args, keys, pkg)) {
+                *group_counts.entry(keys.group(pkg, group_by)).or_insert(0) += 1;
+            }
+        }
+        TextListing {
+            args,
+            keys,
+            use_table,
+            group_counts,
+            current_group: None,
+            rows: Vec::new(),
+        }
+    }
+
+    /// The table view only lists packages with dexopt info, so only those count towards a group.
+    fn shown(args: &Args, keys: &sorting::Keys, pkg: &Package) -> bool {
+        args.verbose || keys.analyzer.get_info(&pkg.name).is_some()
+    }
+
+    /// Adds a package; table rows are buffered until `finish`, other entries are printed right away.
+    fn push(
+        &mut self,
+        stdout: &mut io::Stdout,
+        pkg: &'a Package,
+        label: Option<String>,
+        compilation: Option<Compilation>,
+        highlight: bool,
+    ) -> io::Result<()> {
+        let info_list = self.keys.analyzer.get_info(&pkg.name);
+        let origin = self.keys.origins.origin(pkg);
+        let installer = self.keys.origins.installer(pkg);
+
+        let mut group_header = None;
+        if let Some(group_by) = self.args.group_by
+            && Self::shown(self.args, self.keys, pkg)
+        {
+            let group = self.keys.group(pkg, group_by);
+            if self.current_group.as_ref() != Some(&group) {
+                let count = self.group_counts.get(&group).copied().unwrap_or(0);
+                if self.use_table {
+                    group_header = Some(format!("{} ({})", group, count));
+                } else {
+                    UI::print_group_header(stdout, &group, count)?;
+                }
+                self.current_group = Some(group);
+            }
+        }
+
+        if self.use_table {
+            if let Some(infos) = info_list {
+                self.rows.push(table::Row {
+                    pkg,
+                    label,
+                    infos,
+                    size: infos.iter().filter_map(|i| i.artifact_bytes(pkg)).reduce(|a, b| a + b),
+                    origin,
+                    installer,
+                    compilation,
+                    group: group_header,
+                    highlight,
+                });
+            }
+        } else if self.args.verbose {
+            UI::print_block_entry(stdout, pkg, label.as_deref(), info_list, origin, installer, compilation)?;
+        } else if let Some(infos) = info_list {
+            UI::print_table_entry(stdout, pkg, infos, highlight)?;
+        }
+        Ok(())
+    }
+
+    /// Prints the buffered table, sized to the terminal.
+    fn finish(self, stdout: &mut io::Stdout) -> io::Result<()> {
+        if !self.use_table || self.rows.is_empty() {
+            return Ok(());
+        }
+        let width = terminal_size().map(|(Width(w), _)| w as usize);
+        writeln!(stdout)?;
+        for line in table::render(&self.rows, &self.args.columns, width) {
+            writeln!(stdout, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Single-package actions that can be triggered from the CLI or the TUI.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum PackageAction {
+    /// Clear profiles, then force-compile with 'speed'.
+    Optimize,
+    /// Drop compiled artifacts and return to the install-time state.
+    Reset,
+}
+
+impl PackageAction {
+    /// Shell commands for the action, each with the message to report if it fails.
+    fn commands(self, package: &str) -> Vec<(String, &'static str)> {
+        match self {
+            PackageAction::Optimize => vec![
+                (format!("pm art clear-app-profiles {}", package), "Failed to clear app profiles for"),
+                (format!("cmd package compile -m speed -f {}", package), "Failed to compile"),
+            ],
+            PackageAction::Reset => vec![(format!("cmd package compile --reset {}", package), "Failed to reset")],
+        }
+    }
+
+    /// Runs the action through `su` and returns the failed steps.
+    fn run(self, package: &str) -> Result<Vec<String>> {
+        let mut failures = Vec::new();
+        for (cmd, failure) in self.commands(package) {
+            let output = Command::new("su")
+                .arg("-c")
+                .arg(&cmd)
+                .output()
+                .with_context(|| format!("Failed to execute '{}'", cmd))?;
+
+            if !output.status.success() {
+                failures.push(format!("{} {}", failure, package));
+            }
+        }
+        Ok(failures)
+    }
+}
+
+/// Redraws the summary and package list every `interval` until Ctrl-C.
+fn run_watch(args: &Args, interval: Duration) -> Result<()> {
+    let _interrupts = trap_interrupts()?;
+
+    let prefix = "[-]".cyan();
+    let mut filters = Filters::from_args(args)?;
+    let use_table = !args.verbose;
+    let wants_label = args.verbose || args.sort == SortKey::Label || (use_table && args.columns.contains(&Column::Label));
+    // Labels only change with an update, so each package's label is looked up once
+    let mut labels: HashMap<String, Option<String>> = HashMap::new();
+    let started = Instant::now();
+    let mut previous: Option<Analyzer> = None;
+    let mut refresh = 0;
+
+    while !INTERRUPTED.load(Ordering::SeqCst) {
+        refresh += 1;
+        // Installs and updates change origins and installers while watching
+        if refresh > 1 {
+            filters.refresh_origins(args.r#type)?;
+        }
+        let packages = Package::fetch_list(args.r#type)?;
+        let analyzer = Analyzer::new(&Analyzer::fetch_dump()?);
+        let origins = filters.listing_origins(args.r#type);
+        let changed: HashSet<String> = previous
+            .as_ref()
+            .map(|p| p.diff(&analyzer).into_iter().map(|c| c.package).collect())
+            .unwrap_or_default();
+
+        let mut selected: Vec<&Package> = packages
+            .iter()
+            .filter(|pkg| filters.matches(pkg, analyzer.get_info(&pkg.name)))
+            .collect();
+        if wants_label {
+            let missing: Vec<(String, Option<String>)> = selected
+                .par_iter()
+                .filter(|pkg| !labels.contains_key(&pkg.name))
+                .map(|pkg| (pkg.name.clone(), pkg.get_label()))
+                .collect();
+            labels.extend(missing);
+        }
+        let keys = sorting::Keys {
+            analyzer: &analyzer,
+            labels: selected
+                .iter()
+                .filter_map(|pkg| Some((pkg.name.as_str(), labels.get(&pkg.name)?.clone())))
+                .collect(),
+            origins: &origins,
+        };
+        keys.sort(&mut selected, args.sort, args.group_by);
+
+        let mut summary = Summary::default();
+        let mut compilations: HashMap<&str, Compilation> = HashMap::new();
+        for pkg in &selected {
+            if let Some(infos) = analyzer.get_info(&pkg.name) {
+                summary.total_apps += 1;
+                for info in infos {
+                    summary.count_entry(&info.status, info.reason.as_deref());
+                }
+                summary.count_origin(origins.origin(pkg), origins.installer(pkg));
+                let compilation = Compilation::check(pkg, infos);
+                summary.count_freshness(compilation.freshness);
+                compilations.insert(&pkg.name, compilation);
+            }
+        }
+
+        let mut stdout = io::stdout();
+        if theme::cursor_control(stdout.is_terminal()) {
+            write!(stdout, "\x1b[2J\x1b[H")?;
+        } else if refresh > 1 {
+            writeln!(stdout)?;
+        }
+        writeln!(
+            stdout,
+            "{} Refresh #{} every {}s, watching for {}. Press Ctrl-C to stop.",
+            prefix,
+            refresh,
+            interval.as_secs(),
+            UI::format_duration(started.elapsed().as_secs())
+        )?;
+        UI::print_summary(&summary, args.r#type);
+        if !changed.is_empty() {
+            writeln!(
+                stdout,
+                "\n{} {} package(s) changed since the last refresh.",
+                prefix,
+                changed.len().to_string().yellow().bold()
+            )?;
+        }
+
+        let mut listing = TextListing::new(args, &keys, use_table, &selected);
+        for pkg in &selected {
+            let label = keys.labels.get(pkg.name.as_str()).cloned().flatten();
+            listing.push(&mut stdout, pkg, label, compilations.get(pkg.name.as_str()).copied(), changed.contains(&pkg.name))?;
+        }
+        listing.finish(&mut stdout)?;
+        stdout.flush()?;
+        previous = Some(analyzer);
+
+        let slept = Instant::now();
+        while slept.elapsed() < interval && !INTERRUPTED.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    println!("\n{} Stopped watching.", prefix);
+    Ok(())
+}
+
+/// Runs `bg-dexopt-job` with a live progress bar; Ctrl-C cancels the job instead of abandoning it.
+fn run_bg_dexopt_job(prefix: &ColoredString) -> Result<()> {
+    const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+    let packages = Package::fetch_list(AppType::All)?;
+    let before = Analyzer::new(&Analyzer::fetch_dump()?);
+
+    // Every package with dexopt entries is a candidate; the job does not say which ones it will process
+    let tracked: Vec<(&Package, Vec<PathBuf>)> = packages
+        .iter()
+        .filter_map(|pkg| {
+            let infos = before.get_info(&pkg.name)?;
+            Some((pkg, infos.iter().filter_map(|i| i.artifact_path(pkg)).collect()))
+        })
+        .collect();
+
+    let interrupts = trap_interrupts()?;
+
+    let start_time = SystemTime::now();
+    let started = Instant::now();
+
+    // Own process group, so Ctrl-C reaches only us and we can cancel the job properly
+    let mut child = Command::new("su")
+        .arg("-c")
+        .arg("cmd package bg-dexopt-job")
+        .stdout(Stdio::null())
+        .process_group(0)
+        .spawn()
+        .with_context(|| "Failed to execute background optimization")?;
+
+    let mut last_poll: Option<Instant> = None;
+    let mut seen: HashSet<&str> = HashSet::new();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break Some(status);
+        }
+        if INTERRUPTED.swap(false, Ordering::SeqCst) {
+            UI::clear_progress();
+            println!("{} {}", prefix, "Cancelling background dexopt job...".yellow().bold());
+            if !bg_dexopt::cancel_job() {
+                eprintln!("{} Failed to cancel the job; killing the client instead.", prefix);
+                child.kill().ok();
+            }
+            child.wait().ok();
+            break None;
+        }
+
+        if last_poll.is_none_or(|t| t.elapsed() >= POLL_INTERVAL) {
+            last_poll = Some(Instant::now());
+            // A package is done once its artifacts were rewritten, or once dex2oat ran on it and exited,
+            // which also covers compilations that failed or left the artifacts as they were
+            let inputs = bg_dexopt::running_dex2oat_inputs();
+            let compiling: HashSet<&str> = tracked
+                .iter()
+                .map(|(pkg, _)| *pkg)
+                .filter(|pkg| {
+                    let dir = Path::new(&pkg.path).parent();
+                    inputs.iter().any(|i| Path::new(i).parent() == dir)
+                })
+                .map(|pkg| pkg.name.as_str())
+                .collect();
+            seen.extend(&compiling);
+            let done = tracked
+                .iter()
+                .filter(|(pkg, paths)| {
+                    let name = pkg.name.as_str();
+                    !compiling.contains(name)
+                        && (seen.contains(name)
+                            || paths.iter().any(|p| {
+                                fs::metadata(p)
+                                    .and_then(|m| m.modified())
+                                    .is_ok_and(|t| t >= start_time)
+                            }))
+                })
+                .count();
+            let current = tracked.iter().map(|(pkg, _)| pkg.name.as_str()).find(|name| compiling.contains(name));
+            UI::print_progress(done, tracked.len(), started.elapsed(), current);
+        }
+        thread::sleep(Duration::from_millis(100));
+    };
+    drop(interrupts);
+    UI::clear_progress();
+
+    match status {
+        Some(s) if s.success() => println!(
+            "{} Background dexopt finished in {}.",
+            prefix,
+            UI::format_duration(started.elapsed().as_secs()).green().bold()
+        ),
+        Some(_) => eprintln!("{} Optimization command failed.", prefix),
+        None => println!(
+            "{} Background dexopt cancelled after {}.",
+            prefix,
+            UI::format_duration(started.elapsed().as_secs()).yellow().bold()
+        ),
+    }
+
+    let after = Analyzer::new(&Analyzer::fetch_dump()?);
+    UI::print_status_changes(&before.diff(&after));
+    Ok(())
+}
+
+/// Age of the newest `bg-dexopt` artifact of each package.
+fn bg_dexopt_ages(packages: &[Package], analyzer: &Analyzer) -> Vec<Duration> {
+    // One age per package, so multi-ISA packages are not counted twice
+    packages
+        .iter()
+        .filter_map(|pkg| {
+            analyzer
+                .get_info(&pkg.name)?
+                .iter()
+                .filter(|i| i.reason.as_deref() == Some("bg-dexopt"))
+                .filter_map(|i| i.last_dexopt_age(pkg))
+                .min()
+        })
+        .collect()
+}
+
+/// Unix time at which the last background dexopt run finished.
+///
+/// Like `dexter bg-dexopt`, this prefers the run ART Service recorded in `runs` and only estimates
+/// from artifact ages when there is none.
+fn bg_dexopt_last_run(runs: &[bg_dexopt::JobRun], packages: &[Package], analyzer: &Analyzer) -> Option<u64> {
+    if let Some(run) = runs.last() {
+        return run.finished_at(bg_dexopt::utc_offset_secs());
+    }
+    let run = bg_dexopt::estimate_last_run(&bg_dexopt_ages(packages, analyzer))?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    Some(now.saturating_sub(run.finished_secs_ago))
+}
+
+fn show_bg_dexopt_status(json: bool, output: Option<&Path>) -> Result<()> {
+    let packages = Package::fetch_list(AppType::All)?;
+    let dump = Analyzer::fetch_dump()?;
+    let analyzer = Analyzer::new(&dump);
+
+    let status = BgDexoptStatus::collect(&dump, &bg_dexopt_ages(&packages, &analyzer));
+    if json {
+        let mut out = open_output(output)?;
+        writeln!(out, "{}", serde_json::to_string_pretty(&status)?)?;
+        out.flush()?;
+    } else {
+        UI::print_bg_dexopt_status(&status);
+    }
+    Ok(())
+}
+
+/// Checks every selected dexopt entry's artifacts against its APK and the device's boot image.
+fn verify_artifacts(args: &Args, filters: &Filters) -> Result<()> {
+    let json = args.output_format() == Some(OutputFormat::Json);
+    let packages = Package::fetch_list(args.r#type)?;
+    let analyzer = Analyzer::new(&Analyzer::fetch_dump()?);
+
+    let entries: Vec<(&Package, &DexOptInfo)> = packages
+        .iter()
+        .filter_map(|pkg| Some((pkg, analyzer.get_info(&pkg.name)?)))
+        .filter(|(pkg, infos)| filters.matches(pkg, Some(infos)))
+        .flat_map(|(pkg, infos)| infos.iter().map(move |info| (pkg, info)))
+        .collect();
+    let mut boot_images: BTreeMap<&str, Option<String>> = BTreeMap::new();
+    for (_, info) in &entries {
+        boot_images.entry(&info.isa).or_insert_with(|| artifact::device_boot_image_checksum(&info.isa));
+    }
+
+    let checks: Vec<ArtifactCheck> = entries
+        .par_iter()
+        .filter_map(|(pkg, info)| artifact::check(pkg, info, boot_images[info.isa.as_str()].as_deref()))
+        .collect();
+
+    if json {
+        let report = artifact::VerifyReport {
+            checked: checks.len(),
+            boot_images,
+            rejected: checks.iter().filter(|c| c.is_rejected()).collect(),
+            unverified: checks.iter().filter(|c| c.is_unverified()).collect(),
+        };
+        let mut out = open_output(args.output.as_deref())?;
+        writeln!(out, "{}", serde_json::to_string_pretty(&report)?)?;
+        out.flush()?;
+    } else {
+        UI::print_artifact_checks(&checks, &boot_images);
+    }
+    Ok(())
+}
+
+/// Where machine-readable output goes: the `--output` file, or stdout.
+fn open_output(path: Option<&Path>) -> Result<Box<dyn Write>> {
+    Ok(match path {
+        Some(path) => Box::new(io::BufWriter::new(
+            fs::File::create(path).with_context(|| format!("Failed to create '{}'", path.display()))?,
+        )),
+        None => Box::new(io::stdout().lock()),
+    })
+}
+
 fn check_root() -> Result<()> {
     if !nix::unistd::Uid::current().is_root() {
         eprintln!("{}", "Error: This tool requires root access (su).".red().bold());
@@ -558,8 +2052,34 @@ fn check_root() -> Result<()> {
 }
 
 fn main() -> Result<()> {
-    check_root()?;
     let mut args = Args::parse();
+    colored::control::set_override(args.color.enabled(|name| std::env::var(name).ok(), io::stdout().is_terminal()));
+    let theme = match args.theme.clone().or_else(|| Theme::default_path().filter(|path| path.exists())) {
+        Some(path) => Theme::load(&path)?,
+        None => Theme::default(),
+    };
+    theme::init(args.ascii, args.color, theme);
+    if args.output.is_some() {
+        match args.command {
+            Some(Commands::Tui | Commands::Serve { .. }) => {
+                anyhow::bail!("--output cannot be used with the tui and serve subcommands")
+            }
+            Some(Commands::Schema) => {}
+            Some(Commands::BgDexopt | Commands::Verify) if args.output_format() != Some(OutputFormat::Json) => {
+                anyhow::bail!("--output needs --json with the bg-dexopt and verify subcommands")
+            }
+            _ if args.output_format().is_none() => {
+                anyhow::bail!("--output needs a machine-readable format, given by --json or --format")
+            }
+            _ => {}
+        }
+    }
+    if let Some(Commands::Schema) = args.command {
+        let mut out = open_output(args.output.as_deref())?;
+        out.write_all(report::SCHEMA.as_bytes())?;
+        return Ok(out.flush()?);
+    }
+    check_root()?;
 
     // Adjust behavior when optimization is requested
     if let Some(ref target) = args.optimize {
@@ -572,6 +2092,33 @@ fn main() -> Result<()> {
         }
     }
 
+    match args.command {
+        Some(Commands::BgDexopt) => {
+            return show_bg_dexopt_status(args.output_format() == Some(OutputFormat::Json), args.output.as_deref());
+        }
+        Some(Commands::Tui) => return tui::run(args.r#type, Filters::from_args(&args)?),
+        Some(Commands::Verify) => return verify_artifacts(&args, &Filters::from_args(&args)?),
+        Some(Commands::Serve { ref listen, ref metrics_addr }) => {
+            return serve::run(listen.as_deref(), metrics_addr.as_deref(), args.r#type, Filters::from_args(&args)?);
+        }
+        _ => {}
+    }
+
+    // The device report only needs the summary, not the per-package listing
+    let summary_only = matches!(args.command, Some(Commands::Device));
+    let delimiter = match args.output_format() {
+        Some(OutputFormat::Csv) => Some(Delimiter::Comma),
+        Some(OutputFormat::Tsv) => Some(Delimiter::Tab),
+        _ => None,
+    };
+    if summary_only && delimiter.is_some() {
+        anyhow::bail!("The device report is only available as text or JSON");
+    }
+    if summary_only {
+        args.verbose = false;
+        args.explain = false;
+    }
+
     let prefix = "[-]".cyan();
 
     if let Some(ref target) = args.optimize {
@@ -583,49 +2130,34 @@ fn main() -> Result<()> {
         println!("{} {}", prefix, msg);
 
         if target == "all" {
-            let status = Command::new("su")
-                .arg("-c")
-                .arg("cmd package bg-dexopt-job")
-                .status()
-                .with_context(|| "Failed to execute background optimization")?;
-
-            if !status.success() {
-                eprintln!("{} Optimization command failed.", prefix);
-            }
+            run_bg_dexopt_job(&prefix)?;
         } else {
-            // clear profiles
-            let cmd1 = format!("pm art clear-app-profiles {}", target);
-            let status1 = Command::new("su")
-                .arg("-c")
-                .arg(&cmd1)
-                .status()
-                .with_context(|| "Failed to clear app profiles")?;
-
-            if !status1.success() {
-                eprintln!("{} Failed to clear app profiles for {}", prefix, target);
-            }
-
-            // compile
-            let cmd2 = format!("cmd package compile -m speed -f {}", target);
-            let status2 = Command::new("su")
-                .arg("-c")
-                .arg(&cmd2)
-                .status()
-                .with_context(|| "Failed to compile package")?;
-
-            if !status2.success() {
-                eprintln!("{} Failed to compile {}", prefix, target);
+            for failure in PackageAction::Optimize.run(target)? {
+                eprintln!("{} {}", prefix, failure);
             }
         }
     }
 
-    if !args.json {
+    if let Some(interval) = args.watch {
+        return run_watch(&args, Duration::from_secs(interval.max(1)));
+    }
+
+    let policy = args.policy.as_deref().map(Policy::load).transpose()?;
+    let filters = Filters::from_args(&args)?;
+
+    let json = args.output_format() == Some(OutputFormat::Json);
+    let ndjson = args.output_format() == Some(OutputFormat::Ndjson);
+    let html = args.output_format() == Some(OutputFormat::Html);
+    let markdown = args.output_format() == Some(OutputFormat::Markdown);
+    let prometheus = args.output_format() == Some(OutputFormat::Prometheus);
+    let collect_records = json || html || markdown || prometheus || args.export_sqlite.is_some();
+    if !args.is_machine_output() {
         let msg = "Fetching package list".bold();
         println!("{} {} ({}) ...", prefix, msg, args.r#type);
     }
     let packages = Package::fetch_list(args.r#type)?;
     
-    if !args.json {
+    if !args.is_machine_output() {
         println!("{} Found {} packages.", prefix, packages.len().to_string().green().bold());
         let msg = "Fetching dexopt dump...".bold();
         println!("{} {}", prefix, msg);
@@ -633,78 +2165,188 @@ fn main() -> Result<()> {
     let dump = Analyzer::fetch_dump()?;
     let analyzer = Analyzer::new(&dump);
 
-    if !args.json && !args.verbose {
+    // One row per package; --explain keeps the per-line listing so findings sit under their package
+    let use_table = !args.is_machine_output() && !args.verbose && !args.explain && !summary_only;
+    if !args.is_machine_output() && !args.verbose && !summary_only && !use_table {
         UI::print_header();
     }
 
     let mut stdout = io::stdout();
-    let mut stats: BTreeMap<String, usize> = BTreeMap::new();
-    let mut total_displayed = 0;
-    let mut json_results = Vec::new();
+    let mut out = open_output(args.output.as_deref())?;
+    let delimited = delimiter.map(DelimitedWriter::new);
+    if let Some(ref writer) = delimited {
+        writer.write_header(&mut out)?;
+    }
+    let mut summary = Summary::default();
+    let mut records = Vec::new();
 
     // Filtering Logic
-    let filtered_packages: Vec<&Package> = packages
+    let mut filtered_packages: Vec<&Package> = packages
         .iter()
-        .filter(|pkg| args.filter.as_ref().map_or(true, |f| pkg.name.contains(f)))
+        .filter(|pkg| filters.matches(pkg, analyzer.get_info(&pkg.name)))
         .collect();
 
-    let display_data: Vec<(&Package, Option<String>)> = if args.verbose || args.json {
-        filtered_packages
-            .par_iter()
-            .map(|pkg| (*pkg, pkg.get_label()))
-            .collect()
+    let origins = filters.listing_origins(args.r#type);
+
+    let keys = sorting::Keys {
+        analyzer: &analyzer,
+        labels: if args.sort == SortKey::Label {
+            filtered_packages.par_iter().map(|pkg| (pkg.name.as_str(), pkg.get_label())).collect()
+        } else {
+            HashMap::new()
+        },
+        origins: &origins,
+    };
+    keys.sort(&mut filtered_packages, args.sort, args.group_by);
+
+    let mut listing = (!args.is_machine_output()).then(|| TextListing::new(&args, &keys, use_table, &filtered_packages));
+    let wants_label = args.verbose || args.is_machine_output() || (use_table && args.columns.contains(&Column::Label));
+    // Group of each record, in the same order as `records`
+    let mut record_groups: Vec<String> = Vec::new();
+
+    let device = if args.explain || collect_records || ndjson || summary_only {
+        DeviceProps::fetch().unwrap_or_default()
     } else {
-        filtered_packages.iter().map(|pkg| (*pkg, None)).collect()
+        DeviceProps::default()
+    };
+
+    // Only packages that will actually be explained need their manifest flags
+    let needs_flags = |pkg: &Package| {
+        args.explain
+            && analyzer.get_info(&pkg.name).is_some_and(|infos| {
+                infos.iter().any(|i| explain::EXPLAINED_STATUSES.contains(&i.status.as_str()))
+            })
     };
 
-    for (pkg, app_label) in display_data {
-        let info_list = analyzer.get_info(&pkg.name);
+    // NDJSON consumers see records while later packages are still being looked up
+    let batch_size = if ndjson { rayon::current_num_threads() } else { filtered_packages.len().max(1) };
+
+    for batch in filtered_packages.chunks(batch_size) {
+        let details: Vec<(&Package, Option<String>, Option<ApkFlags>)> = if wants_label || args.explain {
+            batch
+                .par_iter()
+                .map(|pkg| {
+                    let label = match keys.labels.get(pkg.name.as_str()) {
+                        Some(label) => label.clone(),
+                        None if wants_label => pkg.get_label(),
+                        None => None,
+                    };
+                    let flags = if needs_flags(pkg) { pkg.get_apk_flags() } else { None };
+                    (*pkg, label, flags)
+                })
+                .collect()
+        } else {
+            batch.iter().map(|pkg| (*pkg, None, None)).collect()
+        };
+
+        for (pkg, app_label, flags) in details {
+            let info_list = analyzer.get_info(&pkg.name);
 
-        if let Some(infos) = info_list {
-            // Apply Status Filter
-            if let Some(ref status_filter) = args.status {
-                if !infos.iter().any(|i| i.status.contains(status_filter)) {
-                    continue;
+            let origin = origins.origin(pkg);
+            let installer = origins.installer(pkg);
+            let compilation = info_list.map(|infos| Compilation::check(pkg, infos));
+            if let Some(infos) = info_list {
+                summary.total_apps += 1;
+                for info in infos {
+                    summary.count_entry(&info.status, info.reason.as_deref());
                 }
+                summary.count_origin(origin, installer);
+            }
+            if let Some(compilation) = compilation {
+                summary.count_freshness(compilation.freshness);
             }
 
-            total_displayed += 1;
-            for info in infos {
-                *stats.entry(info.status.clone()).or_insert(0) += 1;
+            if summary_only {
+                continue;
             }
-        } else if args.status.is_some() {
-            // If status filter is active but app has no info, skip it
-            continue;
-        }
 
-        if args.json {
-            json_results.push(json!({
-                "package": pkg.name,
-                "label": app_label,
-                "path": pkg.path,
-                "dexopt_info": info_list
-            }));
-        } else {
-            if args.verbose {
-                UI::print_block_entry(&mut stdout, pkg, app_label.as_deref(), info_list)?;
-            } else if let Some(infos) = info_list {
-                for (i, info) in infos.iter().enumerate() {
-                    let colored_raw = UI::colorize_line(&info.raw_line, &info.status);
-                    if i == 0 {
-                        writeln!(stdout, "{} | {}", format!("{:<45}", pkg.name).bright_white(), colored_raw)?;
-                    } else {
-                        writeln!(stdout, "{:<45} | {}", "", colored_raw)?;
+            let findings = match info_list {
+                Some(infos) if args.explain => explain_package(
+                    pkg,
+                    infos,
+                    flags.as_ref(),
+                    origins.installer(pkg),
+                    &device,
+                ),
+                _ => Vec::new(),
+            };
+
+            if let Some(ref writer) = delimited {
+                writer.write_package(&mut out, pkg, app_label.as_deref(), info_list, origin, installer)?;
+            } else if let Some(ref mut listing) = listing {
+                listing.push(&mut stdout, pkg, app_label.clone(), compilation, false)?;
+
+                if !findings.is_empty() {
+                    UI::print_diagnosis(&mut stdout, &findings)?;
+                }
+            }
+
+            if collect_records || ndjson {
+                let mut record = PackageRecord::new(pkg, app_label, info_list);
+                record.origin = Some(origin);
+                record.installer = installer.map(str::to_string);
+                record.set_compilation(compilation);
+                if args.explain {
+                    record.diagnosis = Some(findings);
+                }
+                if ndjson {
+                    writeln!(out, "{}", serde_json::to_string(&Record::Package(&record))?)?;
+                    out.flush()?;
+                }
+                if collect_records {
+                    if let Some(group_by) = args.group_by {
+                        record_groups.push(keys.group(pkg, group_by));
                     }
+                    records.push(record);
                 }
-                writeln!(stdout)?;
             }
         }
     }
 
-    if args.json {
-        println!("{}", serde_json::to_string_pretty(&json_results)?);
+    if let Some(listing) = listing {
+        listing.finish(&mut stdout)?;
+    }
+
+    let device_report = summary_only.then(|| DeviceReport::collect(&device));
+    let report = collect_records.then(|| Report {
+        schema_version: report::SCHEMA_VERSION,
+        metadata: RunMetadata::collect(args.r#type, &filters, &device),
+        summary: summary.clone(),
+        listing: match args.group_by {
+            Some(group_by) => Listing::grouped(group_by, record_groups.into_iter().zip(records)),
+            None => Listing::Flat { packages: records },
+        },
+        device: device_report.clone(),
+    });
+
+    if delimited.is_some() {
+        // Rows were streamed as they were produced
+    } else if ndjson {
+        let record = Record::Summary(Box::new(SummaryRecord {
+            schema_version: report::SCHEMA_VERSION,
+            metadata: RunMetadata::collect(args.r#type, &filters, &device),
+            summary,
+            device: device_report,
+        }));
+        writeln!(out, "{}", serde_json::to_string(&record)?)?;
+    } else if let Some(ref output) = report
+        && args.is_machine_output()
+    {
+        if html {
+            out.write_all(html::render(output).as_bytes())?;
+        } else if markdown {
+            out.write_all(markdown::render(output, args.verbose).as_bytes())?;
+        } else if prometheus {
+            let last_run = bg_dexopt_last_run(&bg_dexopt::parse_job_runs(&dump), &packages, &analyzer);
+            out.write_all(metrics::render(output, last_run).as_bytes())?;
+        } else {
+            writeln!(out, "{}", serde_json::to_string_pretty(output)?)?;
+        }
     } else {
-        UI::print_summary(total_displayed, &stats, args.r#type);
+        UI::print_summary(&summary, args.r#type);
+        if let Some(report) = device_report {
+            UI::print_device_report(&report);
+        }
 
         if args.verbose && !Package::is_aapt_available() {
             println!();
@@ -715,6 +2357,54 @@ fn main() -> Result<()> {
         }
     }
 
+    if let (Some(path), Some(output)) = (&args.export_sqlite, &report) {
+        let apk: HashMap<&str, ApkDetails> = filtered_packages
+            .par_iter()
+            .filter_map(|pkg| Some((pkg.name.as_str(), pkg.get_apk_details()?)))
+            .collect();
+        let run_id = sqlite::export(path, output, &apk, &device)?;
+        if !args.is_machine_output() {
+            println!("{} Exported run #{} to {}", prefix, run_id, path.display());
+        }
+    }
+
+    out.flush()?;
+
+    if let Some(policy) = policy {
+        let user_packages: HashSet<String> = if !policy.distinguishes_user_apps() {
+            HashSet::new()
+        } else if args.r#type == AppType::User {
+            packages.iter().map(|p| p.name.clone()).collect()
+        } else {
+            Package::fetch_list(AppType::User)?.into_iter().map(|p| p.name).collect()
+        };
+        // Filters only narrow the listing, and named packages are checked whatever `-t` selects
+        let unlisted: Vec<&String> = policy.packages.keys().filter(|name| !packages.iter().any(|p| &p.name == *name)).collect();
+        let named = if unlisted.is_empty() {
+            Vec::new()
+        } else {
+            Package::fetch_list(AppType::All)?.into_iter().filter(|p| unlisted.contains(&&p.name)).collect()
+        };
+        let checked: Vec<(&Package, Option<&Vec<DexOptInfo>>)> = packages
+            .iter()
+            .chain(&named)
+            .map(|pkg| (pkg, analyzer.get_info(&pkg.name)))
+            .collect();
+
+        let violations = policy.evaluate(&checked, &user_packages);
+        // Keep machine-readable stdout parseable
+        if args.is_machine_output() && args.output.is_none() {
+            UI::print_policy_violations(&mut io::stderr(), &violations)?;
+        } else {
+            UI::print_policy_violations(&mut io::stdout(), &violations)?;
+        }
+
+        let code = policy::exit_code(&violations);
+        if code != 0 {
+            std::process::exit(code);
+        }
+    }
+
     Ok(())
 }
 
@@ -742,4 +2432,56 @@ mod tests {
         
         assert!(analyzer.get_info("non.existent").is_none());
     }
+
+    #[test]
+    fn test_dex_path_parsing() {
+        let analyzer = Analyzer::new(
+            "[com.example.app]\n  path: /data/app/com.example.app/base.apk\n  arm64: [status=speed-profile] [reason=bg-dexopt] [primary-abi]\n  path: /data/app/com.example.app/split_config.arm64_v8a.apk\n  arm64: [status=verify] [reason=install]\n[com.other]\n  arm64: [status=verify] [reason=install]\n",
+        );
+
+        let infos = analyzer.get_info("com.example.app").unwrap();
+        assert_eq!(infos.len(), 2);
+        assert!(infos[0].primary_abi);
+        assert_eq!(infos[0].dex_path.as_deref(), Some("/data/app/com.example.app/base.apk"));
+        assert_eq!(infos[0].split_name().as_deref(), Some("base"));
+        assert!(!infos[1].primary_abi);
+        assert_eq!(infos[1].split_name().as_deref(), Some("config.arm64_v8a"));
+        // A new package starts without a dex path
+        let other = analyzer.get_info("com.other").unwrap();
+        assert!(other[0].dex_path.is_none());
+        assert!(other[0].split_name().is_none());
+    }
+
+    #[test]
+    fn test_location_parsing() {
+        let analyzer = Analyzer::new(
+            "[com.example.app]\n  arm64: [status=speed-profile] [reason=bg-dexopt]\n    [location is /data/app/com.example.app/oat/arm64/base.odex]\n  arm: [status=verify] [reason=install]\n",
+        );
+
+        let infos = analyzer.get_info("com.example.app").unwrap();
+        assert_eq!(infos.len(), 2);
+        assert_eq!(infos[0].isa, "arm64");
+        assert_eq!(infos[0].reason.as_deref(), Some("bg-dexopt"));
+        assert_eq!(infos[0].location.as_deref(), Some("/data/app/com.example.app/oat/arm64/base.odex"));
+        // The location line belongs to the entry above it only
+        assert!(infos[1].location.is_none());
+    }
+
+    #[test]
+    fn test_analyzer_diff() {
+        let before = Analyzer::new(
+            "[com.a]\n  arm64: [status=verify] [reason=install]\n[com.b]\n  arm64: [status=speed] [reason=cmdline]\n",
+        );
+        let after = Analyzer::new(
+            "[com.a]\n  arm64: [status=speed-profile] [reason=bg-dexopt]\n  arm: [status=verify] [reason=install]\n[com.b]\n  arm64: [status=speed] [reason=cmdline]\n[com.c]\n  arm64: [status=verify] [reason=install]\n",
+        );
+
+        let changes = before.diff(&after);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].package, "com.a");
+        assert_eq!(changes[0].before.as_deref(), Some("verify"));
+        assert_eq!(changes[0].after.as_deref(), Some("speed-profile/verify"));
+        assert_eq!(changes[1].package, "com.c");
+        assert!(changes[1].before.is_none());
+    }
 }
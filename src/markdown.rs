@@ -0,0 +1,79 @@
+use crate::report::Report;
+use std::fmt::Write;
+
+/// Makes text safe for a GFM table cell: pipes are escaped and line breaks flattened.
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|").replace(['\r', '\n'], " ")
+}
+
+/// Escapes the characters that would otherwise start HTML inside `<summary>`.
+fn html_text(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Renders the summary and package table as GitHub-flavored Markdown.
+///
+/// With `details`, each package is followed by a collapsible block holding its raw dexopt lines.
+pub fn render(report: &Report, details: bool) -> String {
+    let mut md = String::new();
+    let meta = &report.metadata;
+    let summary = &report.summary;
+
+    let _ = writeln!(md, "## Dexopt report\n");
+    let mut facts = vec![format!("**{}** apps", meta.app_type), format!("**{}** packages", summary.total_apps)];
+    if let Some(ref model) = meta.device_model {
+        facts.push(match meta.sdk {
+            Some(ref sdk) => format!("{} (SDK {})", model, sdk),
+            None => model.clone(),
+        });
+    }
+    facts.push(meta.generated_at.clone());
+    let _ = writeln!(md, "{}\n", facts.join(" · "));
+
+    let entries: usize = summary.statuses.values().sum();
+    let _ = writeln!(md, "| Status | Count | Share |\n| --- | ---: | ---: |");
+    for (status, count) in &summary.statuses {
+        let share = *count as f64 * 100.0 / entries.max(1) as f64;
+        let _ = writeln!(md, "| `{}` | {} | {:.1}% |", cell(status), count, share);
+    }
+
+    let _ = writeln!(md, "\n### Packages\n");
+    let _ = writeln!(md, "| Package | Label | ISA | Status | Reason |\n| --- | --- | --- | --- | --- |");
+    for pkg in report.packages() {
+        for entry in &pkg.dexopt {
+            let _ = writeln!(
+                md,
+                "| `{}` | {} | {} | `{}` | {} |",
+                cell(pkg.package),
+                cell(pkg.label.as_deref().unwrap_or_default()),
+                cell(entry.isa),
+                cell(entry.status),
+                entry.reason.map(cell).unwrap_or_default()
+            );
+        }
+    }
+
+    if details {
+        let _ = writeln!(md, "\n### Details\n");
+        for pkg in report.packages().filter(|p| !p.dexopt.is_empty()) {
+            let _ = writeln!(md, "<details>\n<summary>{}</summary>\n\n```", html_text(pkg.package));
+            for entry in &pkg.dexopt {
+                let _ = writeln!(md, "{}", entry.raw);
+            }
+            let _ = writeln!(md, "```\n\n</details>\n");
+        }
+    }
+
+    md
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cell_escaping() {
+        assert_eq!(cell("a|b\nc"), "a\\|b c");
+        assert_eq!(html_text("<pkg & co>"), "&lt;pkg &amp; co&gt;");
+    }
+}
@@ -0,0 +1,152 @@
+use crate::report::{DexoptEntry, PackageRecord, Report};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Content type of the Prometheus text exposition format.
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+fn label_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn header(out: &mut String, name: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+}
+
+/// File name of the entry's dex file (`base.apk`, `split_config.arm64.apk`, ...), so split APKs
+/// get series of their own; the directory is left out as it changes with every update.
+fn dex_name<'a>(pkg: &'a PackageRecord, entry: &'a DexoptEntry) -> &'a str {
+    let path = entry.dex_path.unwrap_or(pkg.path);
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Renders the report as Prometheus gauges.
+///
+/// `bg_dexopt_last_run` is the Unix time at which the last background dexopt run finished.
+pub fn render(report: &Report, bg_dexopt_last_run: Option<u64>) -> String {
+    let mut out = String::new();
+    let app_type = label_value(&report.metadata.app_type.to_lowercase());
+
+    // A package counts once per status, even when several ISAs share it
+    let mut packages: BTreeMap<&str, usize> = BTreeMap::new();
+    for pkg in report.packages() {
+        let mut statuses: Vec<&str> = pkg.dexopt.iter().map(|e| e.status).collect();
+        statuses.sort_unstable();
+        statuses.dedup();
+        for status in statuses {
+            *packages.entry(status).or_insert(0) += 1;
+        }
+    }
+
+    header(&mut out, "dexter_packages_total", "Packages with at least one dexopt entry in the given status.");
+    for (status, count) in &packages {
+        let _ = writeln!(
+            out,
+            "dexter_packages_total{{status=\"{}\",type=\"{}\"}} {}",
+            label_value(status),
+            app_type,
+            count
+        );
+    }
+
+    header(&mut out, "dexter_package_info", "Compiler filter and compilation reason of each dex file and ISA.");
+    for pkg in report.packages() {
+        for entry in &pkg.dexopt {
+            let _ = writeln!(
+                out,
+                "dexter_package_info{{package=\"{}\",dex=\"{}\",isa=\"{}\",status=\"{}\",reason=\"{}\"}} 1",
+                label_value(pkg.package),
+                label_value(dex_name(pkg, entry)),
+                label_value(entry.isa),
+                label_value(entry.status),
+                label_value(entry.reason.unwrap_or_default())
+            );
+        }
+    }
+
+    header(&mut out, "dexter_artifact_bytes", "Combined size of the .odex, .vdex and .art files.");
+    for pkg in report.packages() {
+        for entry in &pkg.dexopt {
+            if let Some(bytes) = entry.artifact_bytes {
+                let _ = writeln!(
+                    out,
+                    "dexter_artifact_bytes{{package=\"{}\",dex=\"{}\",isa=\"{}\"}} {}",
+                    label_value(pkg.package),
+                    label_value(dex_name(pkg, entry)),
+                    label_value(entry.isa),
+                    bytes
+                );
+            }
+        }
+    }
+
+    if let Some(timestamp) = bg_dexopt_last_run {
+        header(
+            &mut out,
+            "dexter_bg_dexopt_last_run_timestamp_seconds",
+            "Unix time at which the last background dexopt run finished, from ART Service's job history or else estimated from artifact timestamps.",
+        );
+        let _ = writeln!(out, "dexter_bg_dexopt_last_run_timestamp_seconds {}", timestamp);
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::DeviceProps;
+    use crate::report::{Listing, RunMetadata, Summary};
+    use crate::{AppType, DexOptInfo, Filters, Package};
+
+    #[test]
+    fn test_label_escaping() {
+        assert_eq!(label_value("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+
+    #[test]
+    fn test_split_apks_get_own_series() {
+        let dir = "/data/app/~~a==/com.example.app-b==";
+        let pkg = Package { name: "com.example.app".into(), path: format!("{}/base.apk", dir) };
+        let info = |apk: &str| DexOptInfo {
+            raw_line: String::new(),
+            isa: "arm64".into(),
+            status: "speed-profile".into(),
+            reason: Some("bg-dexopt".into()),
+            primary_abi: true,
+            dex_path: Some(format!("{}/{}", dir, apk)),
+            location: None,
+        };
+        let infos = vec![info("base.apk"), info("split_config.arm64.apk")];
+        let mut record = PackageRecord::new(&pkg, None, Some(&infos));
+        record.dexopt[0].artifact_bytes = Some(4096);
+        record.dexopt[1].artifact_bytes = Some(512);
+        let records = vec![record];
+        let report = Report {
+            schema_version: crate::report::SCHEMA_VERSION,
+            metadata: RunMetadata::collect(AppType::User, &Filters::default(), &DeviceProps::default()),
+            summary: Summary::from_records(&records),
+            listing: Listing::Flat { packages: records },
+            device: None,
+        };
+
+        let text = render(&report, None);
+        let series = |name: &str| text.lines().filter(|l| l.starts_with(name)).collect::<Vec<_>>();
+        assert_eq!(
+            series("dexter_package_info{"),
+            [
+                "dexter_package_info{package=\"com.example.app\",dex=\"base.apk\",isa=\"arm64\",status=\"speed-profile\",reason=\"bg-dexopt\"} 1",
+                "dexter_package_info{package=\"com.example.app\",dex=\"split_config.arm64.apk\",isa=\"arm64\",status=\"speed-profile\",reason=\"bg-dexopt\"} 1",
+            ]
+        );
+        assert_eq!(
+            series("dexter_artifact_bytes{"),
+            [
+                "dexter_artifact_bytes{package=\"com.example.app\",dex=\"base.apk\",isa=\"arm64\"} 4096",
+                "dexter_artifact_bytes{package=\"com.example.app\",dex=\"split_config.arm64.apk\",isa=\"arm64\"} 512",
+            ]
+        );
+        assert_eq!(series("dexter_packages_total{"), ["dexter_packages_total{status=\"speed-profile\",type=\"user\"} 1"]);
+    }
+}
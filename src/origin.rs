@@ -0,0 +1,126 @@
+use crate::{AppType, Package};
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Where a package's APK comes from.
+///
+/// Updated system apps are preinstalled packages whose current APK lives in `/data/app`: they are
+/// compiled like user apps, but fall back to the factory APK (and its artifacts) when uninstalled.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, ValueEnum, Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Origin {
+    System,
+    SystemExt,
+    Product,
+    Vendor,
+    Odm,
+    Apex,
+    Data,
+    UpdatedSystem,
+    Unknown,
+}
+
+impl Origin {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Origin::System => "system",
+            Origin::SystemExt => "system-ext",
+            Origin::Product => "product",
+            Origin::Vendor => "vendor",
+            Origin::Odm => "odm",
+            Origin::Apex => "apex",
+            Origin::Data => "data",
+            Origin::UpdatedSystem => "updated-system",
+            Origin::Unknown => "unknown",
+        }
+    }
+
+    /// Classifies an APK path; `is_system` tells updated system apps apart from user apps in `/data`.
+    pub fn classify(path: &str, is_system: bool) -> Self {
+        // Older devices mount the other partitions below /system as well
+        const PREFIXES: &[(&str, Origin)] = &[
+            ("/system_ext/", Origin::SystemExt),
+            ("/system/system_ext/", Origin::SystemExt),
+            ("/product/", Origin::Product),
+            ("/system/product/", Origin::Product),
+            ("/vendor/", Origin::Vendor),
+            ("/system/vendor/", Origin::Vendor),
+            ("/odm/", Origin::Odm),
+            ("/vendor/odm/", Origin::Odm),
+            ("/system/", Origin::System),
+            ("/apex/", Origin::Apex),
+        ];
+
+        if path.starts_with("/data/apex/") || path.starts_with("/system/apex/") {
+            return Origin::Apex;
+        }
+        if path.starts_with("/data/") || path.starts_with("/mnt/expand/") {
+            return if is_system { Origin::UpdatedSystem } else { Origin::Data };
+        }
+        PREFIXES
+            .iter()
+            .filter(|(prefix, _)| path.starts_with(prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(Origin::Unknown, |(_, origin)| *origin)
+    }
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Which packages are preinstalled and who installed each package, fetched once per run.
+#[derive(Debug, Default)]
+pub struct Origins {
+    /// Set for `-t system`, where every analyzed package is preinstalled.
+    all_system: bool,
+    system: HashSet<String>,
+    installers: HashMap<String, String>,
+}
+
+impl Origins {
+    pub fn fetch(app_type: AppType) -> Result<Self> {
+        let system = match app_type {
+            AppType::User | AppType::System => HashSet::new(),
+            _ => Package::fetch_list(AppType::System)?.into_iter().map(|p| p.name).collect(),
+        };
+        Ok(Origins {
+            all_system: app_type == AppType::System,
+            system,
+            installers: Package::fetch_installers()?,
+        })
+    }
+
+    pub fn origin(&self, pkg: &Package) -> Origin {
+        let is_system = self.all_system || self.system.contains(&pkg.name);
+        Origin::classify(&pkg.path, is_system)
+    }
+
+    /// Package that installed `pkg`; preinstalled and sideloaded apps usually have none.
+    pub fn installer(&self, pkg: &Package) -> Option<&str> {
+        self.installers.get(&pkg.name).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(Origin::classify("/system/app/Sys/Sys.apk", true), Origin::System);
+        assert_eq!(Origin::classify("/system/product/app/P/P.apk", true), Origin::Product);
+        assert_eq!(Origin::classify("/system_ext/priv-app/S/S.apk", true), Origin::SystemExt);
+        assert_eq!(Origin::classify("/vendor/odm/app/O/O.apk", true), Origin::Odm);
+        assert_eq!(Origin::classify("/apex/com.android.tzdata/app/T.apk", true), Origin::Apex);
+        assert_eq!(Origin::classify("/data/apex/active/com.android.art@341111000.apex", true), Origin::Apex);
+        assert_eq!(Origin::classify("/data/app/~~a==/com.a-b==/base.apk", false), Origin::Data);
+        assert_eq!(Origin::classify("/data/app/~~a==/com.google.maps-b==/base.apk", true), Origin::UpdatedSystem);
+        assert_eq!(Origin::classify("/oem/app/X.apk", true), Origin::Unknown);
+    }
+}
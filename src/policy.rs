@@ -0,0 +1,250 @@
+use crate::compiler_filter::CompilerFilter;
+use crate::{DexOptInfo, Package};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Expectations that apply to a whole group of packages.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Expectations {
+    /// Every entry must be compiled at least this well.
+    pub min_status: Option<CompilerFilter>,
+    /// No entry may be in one of these statuses.
+    pub forbid_status: Vec<String>,
+    /// Packages that the two rules above do not apply to.
+    pub exempt: Vec<String>,
+}
+
+/// Expectations for one named package.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PackageExpectation {
+    pub status: Option<String>,
+    pub min_status: Option<CompilerFilter>,
+}
+
+/// A `--policy` file, in TOML or YAML.
+///
+/// ```toml
+/// [all]
+/// forbid_status = ["error"]
+///
+/// [user]
+/// min_status = "speed-profile"
+///
+/// [packages."com.example.app"]
+/// status = "speed"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Policy {
+    pub all: Expectations,
+    pub user: Expectations,
+    pub system: Expectations,
+    pub packages: BTreeMap<String, PackageExpectation>,
+}
+
+/// Kinds of violations, in the order their exit codes take precedence.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ViolationKind {
+    Forbidden,
+    MissingPackage,
+    PackageMismatch,
+    BelowMinimum,
+}
+
+impl ViolationKind {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ViolationKind::Forbidden => 10,
+            ViolationKind::MissingPackage => 11,
+            ViolationKind::PackageMismatch => 12,
+            ViolationKind::BelowMinimum => 13,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Violation {
+    pub kind: ViolationKind,
+    pub package: String,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.package, self.message)
+    }
+}
+
+impl Policy {
+    /// Loads a policy, picking the format from the extension (`.toml`, `.yaml`, `.yml`).
+    pub fn load(path: &Path) -> Result<Self> {
+        let raw = fs::read_to_string(path).with_context(|| format!("Failed to read policy '{}'", path.display()))?;
+        let policy: Policy = match path.extension().and_then(|e| e.to_str()) {
+            Some("yaml" | "yml") => serde_yaml::from_str(&raw).with_context(|| format!("Invalid policy '{}'", path.display()))?,
+            _ => toml::from_str(&raw).with_context(|| format!("Invalid policy '{}'", path.display()))?,
+        };
+        Ok(policy)
+    }
+
+    /// Whether evaluation needs to know which packages are user apps.
+    pub fn distinguishes_user_apps(&self) -> bool {
+        self.user.min_status.is_some() || !self.user.forbid_status.is_empty()
+            || self.system.min_status.is_some() || !self.system.forbid_status.is_empty()
+    }
+
+    /// Checks the analyzed packages; `user_packages` names the third-party apps among them.
+    pub fn evaluate(&self, packages: &[(&Package, Option<&Vec<DexOptInfo>>)], user_packages: &HashSet<String>) -> Vec<Violation> {
+        let mut violations = Vec::new();
+
+        for (pkg, infos) in packages {
+            let group = if user_packages.contains(&pkg.name) { &self.user } else { &self.system };
+            let entries = checked_entries(infos.map(Vec::as_slice).unwrap_or_default());
+
+            for expectations in [&self.all, group] {
+                if expectations.exempt.contains(&pkg.name) {
+                    continue;
+                }
+                for info in &entries {
+                    if expectations.forbid_status.contains(&info.status) {
+                        violations.push(Violation {
+                            kind: ViolationKind::Forbidden,
+                            package: pkg.name.clone(),
+                            message: format!("{} is '{}', which the policy forbids", info.isa, info.status),
+                        });
+                    }
+                    if let Some(min) = expectations.min_status
+                        && !at_least(&info.status, min)
+                    {
+                        violations.push(Violation {
+                            kind: ViolationKind::BelowMinimum,
+                            package: pkg.name.clone(),
+                            message: format!("{} is '{}', expected at least '{}'", info.isa, info.status, min),
+                        });
+                    }
+                }
+            }
+        }
+
+        for (name, expected) in &self.packages {
+            let Some((_, infos)) = packages.iter().find(|(p, _)| &p.name == name) else {
+                violations.push(Violation {
+                    kind: ViolationKind::MissingPackage,
+                    package: name.clone(),
+                    message: "not installed or not part of the analyzed packages".to_string(),
+                });
+                continue;
+            };
+            let entries = checked_entries(infos.map(Vec::as_slice).unwrap_or_default());
+            if entries.is_empty() {
+                violations.push(Violation {
+                    kind: ViolationKind::MissingPackage,
+                    package: name.clone(),
+                    message: "no dexopt information found".to_string(),
+                });
+            }
+            for info in entries {
+                if let Some(ref status) = expected.status
+                    && &info.status != status
+                {
+                    violations.push(Violation {
+                        kind: ViolationKind::PackageMismatch,
+                        package: name.clone(),
+                        message: format!("{} is '{}', expected '{}'", info.isa, info.status, status),
+                    });
+                }
+                if let Some(min) = expected.min_status
+                    && !at_least(&info.status, min)
+                {
+                    violations.push(Violation {
+                        kind: ViolationKind::PackageMismatch,
+                        package: name.clone(),
+                        message: format!("{} is '{}', expected at least '{}'", info.isa, info.status, min),
+                    });
+                }
+            }
+        }
+
+        violations
+    }
+}
+
+/// Entries the policy applies to: the primary ABI when the dump marks one, otherwise every ISA.
+fn checked_entries(infos: &[DexOptInfo]) -> Vec<&DexOptInfo> {
+    let primary: Vec<&DexOptInfo> = infos.iter().filter(|i| i.primary_abi).collect();
+    if primary.is_empty() { infos.iter().collect() } else { primary }
+}
+
+/// Statuses that are not compiler filters, like `run-from-apk`, never meet a minimum.
+fn at_least(status: &str, min: CompilerFilter) -> bool {
+    CompilerFilter::parse(status).is_some_and(|filter| filter >= min)
+}
+
+/// Exit code for a set of violations: the code of the most important kind, or 0.
+pub fn exit_code(violations: &[Violation]) -> i32 {
+    violations.iter().map(|v| v.kind).min().map(ViolationKind::exit_code).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(status: &str) -> DexOptInfo {
+        DexOptInfo {
+            raw_line: String::new(),
+            isa: "arm64".into(),
+            status: status.into(),
+            reason: None,
+            primary_abi: true,
+            dex_path: None,
+            location: None,
+        }
+    }
+
+    fn pkg(name: &str) -> Package {
+        Package { name: name.into(), path: String::new() }
+    }
+
+    #[test]
+    fn test_toml_and_yaml_agree() {
+        let toml: Policy = toml::from_str("[user]\nmin_status = \"speed-profile\"\n[packages.\"com.a\"]\nstatus = \"speed\"\n").unwrap();
+        let yaml: Policy = serde_yaml::from_str("user:\n  min_status: speed-profile\npackages:\n  com.a:\n    status: speed\n").unwrap();
+        assert_eq!(toml.user.min_status, yaml.user.min_status);
+        assert_eq!(toml.packages["com.a"].status, yaml.packages["com.a"].status);
+        assert_eq!(toml.user.min_status, Some(CompilerFilter::SpeedProfile));
+        assert!(toml::from_str::<Policy>("[user]\nmin_stauts = \"speed\"\n").is_err());
+        assert!(toml::from_str::<Policy>("[user]\nmin_status = \"fast\"\n").is_err());
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let policy: Policy = toml::from_str(
+            "[all]\nforbid_status = [\"error\"]\n[user]\nmin_status = \"speed-profile\"\nexempt = [\"com.b\"]\n[packages.\"com.c\"]\nstatus = \"speed\"\n[packages.\"com.missing\"]\nstatus = \"speed\"\n",
+        )
+        .unwrap();
+        let (a, b, c, sys) = (pkg("com.a"), pkg("com.b"), pkg("com.c"), pkg("android"));
+        let (verify, error, speed, profile) = (vec![info("verify")], vec![info("error")], vec![info("speed")], vec![info("speed-profile")]);
+        let packages = [(&a, Some(&verify)), (&b, Some(&error)), (&c, Some(&profile)), (&sys, Some(&speed))];
+        let users: HashSet<String> = ["com.a", "com.b", "com.c"].iter().map(|s| s.to_string()).collect();
+
+        let violations = policy.evaluate(&packages, &users);
+        let kinds: Vec<(&str, ViolationKind)> = violations.iter().map(|v| (v.package.as_str(), v.kind)).collect();
+        assert_eq!(
+            kinds,
+            [
+                ("com.a", ViolationKind::BelowMinimum),
+                ("com.b", ViolationKind::Forbidden),
+                ("com.c", ViolationKind::PackageMismatch),
+                ("com.missing", ViolationKind::MissingPackage),
+            ]
+        );
+        assert_eq!(exit_code(&violations), 10);
+        assert_eq!(exit_code(&[]), 0);
+    }
+}
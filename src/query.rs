@@ -0,0 +1,437 @@
+//! The `--where` expression language.
+//!
+//! ```text
+//! expr       := or
+//! or         := and (("or" | "||") and)*
+//! and        := unary (("and" | "&&") unary)*
+//! unary      := ("not" | "!") unary | "(" expr ")" | comparison
+//! comparison := field op value
+//! field      := name | label | status | reason | isa | installer | origin | size
+//! op         := "==" | "!=" | "=~" | "!~" | "like" | "<" | "<=" | ">" | ">="
+//! ```
+//!
+//! `=~` matches a regex, `like` a glob. Sizes accept `K`, `M` and `G` suffixes (powers of 1024);
+//! statuses compare by optimization level (`status >= speed-profile`).
+//! An expression matches a package when it holds for at least one of its dexopt entries.
+
+use crate::compiler_filter::CompilerFilter;
+use crate::origin::{Origin, Origins};
+use crate::{DexOptInfo, Package};
+use anyhow::{Context, Result, bail};
+use globset::{Glob, GlobMatcher};
+use regex::Regex;
+use std::cell::OnceCell;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Field {
+    Name,
+    Label,
+    Status,
+    Reason,
+    Isa,
+    Installer,
+    Origin,
+    Size,
+}
+
+impl Field {
+    fn parse(word: &str) -> Option<Self> {
+        Some(match word.to_ascii_lowercase().as_str() {
+            "name" | "package" => Field::Name,
+            "label" => Field::Label,
+            "status" | "filter" => Field::Status,
+            "reason" => Field::Reason,
+            "isa" => Field::Isa,
+            "installer" => Field::Installer,
+            "origin" | "partition" => Field::Origin,
+            "size" => Field::Size,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Matcher {
+    Equals(String),
+    Regex(Regex),
+    Glob(GlobMatcher),
+    /// Ordering against a size in bytes.
+    Compare(std::cmp::Ordering, bool, u64),
+    /// Ordering against a compiler filter; statuses that are not filters never match.
+    Level(std::cmp::Ordering, bool, CompilerFilter),
+}
+
+#[derive(Clone, Debug)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Test { field: Field, matcher: Matcher, negate: bool },
+}
+
+/// The package being filtered; the label is only read from the APK if the expression needs it.
+pub struct Subject<'a> {
+    pub package: &'a Package,
+    pub infos: Option<&'a Vec<DexOptInfo>>,
+    pub installer: Option<&'a str>,
+    pub origin: Option<Origin>,
+    label: OnceCell<Option<String>>,
+}
+
+impl<'a> Subject<'a> {
+    pub fn new(
+        package: &'a Package,
+        infos: Option<&'a Vec<DexOptInfo>>,
+        installer: Option<&'a str>,
+        origin: Option<Origin>,
+    ) -> Self {
+        Subject { package, infos, installer, origin, label: OnceCell::new() }
+    }
+
+    fn label(&self) -> Option<&str> {
+        self.label.get_or_init(|| self.package.get_label()).as_deref()
+    }
+}
+
+impl Expr {
+    /// Whether any part of the expression looks at `field`.
+    pub fn uses(&self, field: Field) -> bool {
+        match self {
+            Expr::And(a, b) | Expr::Or(a, b) => a.uses(field) || b.uses(field),
+            Expr::Not(e) => e.uses(field),
+            Expr::Test { field: f, .. } => *f == field,
+        }
+    }
+
+    pub fn matches(&self, subject: &Subject) -> bool {
+        match subject.infos.map(Vec::as_slice).unwrap_or_default() {
+            [] => self.eval(subject, None),
+            entries => entries.iter().any(|e| self.eval(subject, Some(e))),
+        }
+    }
+
+    fn eval(&self, subject: &Subject, entry: Option<&DexOptInfo>) -> bool {
+        match self {
+            Expr::And(a, b) => a.eval(subject, entry) && b.eval(subject, entry),
+            Expr::Or(a, b) => a.eval(subject, entry) || b.eval(subject, entry),
+            Expr::Not(e) => !e.eval(subject, entry),
+            Expr::Test { field, matcher, negate } => {
+                let result = match field {
+                    Field::Size => {
+                        let size = entry.and_then(|e| e.artifact_bytes(subject.package));
+                        match (matcher, size) {
+                            (Matcher::Compare(ordering, or_equal, bytes), Some(size)) => {
+                                size.cmp(bytes) == *ordering || (*or_equal && size == *bytes)
+                            }
+                            (Matcher::Equals(value), Some(size)) => parse_size(value).is_some_and(|b| b == size),
+                            _ => false,
+                        }
+                    }
+                    _ => {
+                        let value = match field {
+                            Field::Name => Some(subject.package.name.as_str()),
+                            Field::Label => subject.label(),
+                            Field::Status => entry.map(|e| e.status.as_str()),
+                            Field::Reason => entry.and_then(|e| e.reason.as_deref()),
+                            Field::Isa => entry.map(|e| e.isa.as_str()),
+                            Field::Installer => subject.installer,
+                            Field::Origin => subject.origin.map(Origin::as_str),
+                            Field::Size => unreachable!(),
+                        };
+                        value.is_some_and(|v| match matcher {
+                            Matcher::Equals(expected) => v == expected,
+                            Matcher::Regex(re) => re.is_match(v),
+                            Matcher::Glob(glob) => glob.is_match(v),
+                            Matcher::Level(ordering, or_equal, level) => CompilerFilter::parse(v)
+                                .is_some_and(|f| f.cmp(level) == *ordering || (*or_equal && f == *level)),
+                            Matcher::Compare(..) => false,
+                        })
+                    }
+                };
+                result != *negate
+            }
+        }
+    }
+}
+
+fn parse_size(raw: &str) -> Option<u64> {
+    let raw = raw.trim();
+    let split = raw.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(raw.len());
+    let (number, unit) = raw.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        _ => return None,
+    };
+    Some((number * multiplier as f64) as u64)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Op(&'static str),
+    Open,
+    Close,
+}
+
+const OPERATORS: &[&str] = &["==", "!=", "=~", "!~", "<=", ">=", "&&", "||", "<", ">", "=", "!"];
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = input.trim_start();
+
+    while let Some(c) = rest.chars().next() {
+        if c == '(' || c == ')' {
+            tokens.push(if c == '(' { Token::Open } else { Token::Close });
+            rest = &rest[1..];
+        } else if c == '"' || c == '\'' {
+            let mut value = String::new();
+            let mut chars = rest[1..].char_indices();
+            let mut end = None;
+            while let Some((i, ch)) = chars.next() {
+                match ch {
+                    '\\' => {
+                        if let Some((_, next)) = chars.next() {
+                            // Keep escapes other than the quote itself, regexes need them
+                            if next != c {
+                                value.push('\\');
+                            }
+                            value.push(next);
+                        }
+                    }
+                    _ if ch == c => {
+                        end = Some(i + 2);
+                        break;
+                    }
+                    _ => value.push(ch),
+                }
+            }
+            let end = end.with_context(|| format!("Unterminated string in --where: {}", rest))?;
+            tokens.push(Token::Quoted(value));
+            rest = &rest[end..];
+        } else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        } else {
+            let end = rest
+                .find(|c: char| c.is_whitespace() || "()\"'=!<>&|".contains(c))
+                .unwrap_or(rest.len());
+            if end == 0 {
+                bail!("Unexpected character in --where expression: {}", rest);
+            }
+            tokens.push(Token::Word(rest[..end].to_string()));
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+
+    Ok(tokens)
+}
+
+fn describe(token: Option<&Token>) -> String {
+    match token {
+        Some(Token::Word(w)) => format!("'{}'", w),
+        Some(Token::Quoted(q)) => format!("\"{}\"", q),
+        Some(Token::Op(op)) => format!("'{}'", op),
+        Some(Token::Open) => "'('".to_string(),
+        Some(Token::Close) => "')'".to_string(),
+        None => "end of expression".to_string(),
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn keyword(&mut self, word: &str, op: &str) -> bool {
+        let found = match self.peek() {
+            Some(Token::Word(w)) => w.eq_ignore_ascii_case(word),
+            Some(Token::Op(o)) => *o == op,
+            _ => false,
+        };
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut expr = self.and()?;
+        while self.keyword("or", "||") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut expr = self.unary()?;
+        while self.keyword("and", "&&") {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.keyword("not", "!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.peek() == Some(&Token::Open) {
+            self.pos += 1;
+            let expr = self.or()?;
+            if self.next() != Some(Token::Close) {
+                bail!("Expected ')' in --where expression");
+            }
+            return Ok(expr);
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr> {
+        let field = match self.next() {
+            Some(Token::Word(w)) => Field::parse(&w).with_context(|| {
+                format!("Unknown field '{}' (expected name, label, status, reason, isa, installer, origin or size)", w)
+            })?,
+            other => bail!("Expected a field name in --where expression, found {}", describe(other.as_ref())),
+        };
+        let op = match self.next() {
+            Some(Token::Op(op)) => op.to_string(),
+            Some(Token::Word(w)) if w.eq_ignore_ascii_case("like") => "like".to_string(),
+            other => bail!("Expected an operator after {:?}, found {}", field, describe(other.as_ref())),
+        };
+        let value = match self.next() {
+            Some(Token::Word(v) | Token::Quoted(v)) => v,
+            other => bail!("Expected a value after '{}', found {}", op, describe(other.as_ref())),
+        };
+
+        let (matcher, negate) = match op.as_str() {
+            "==" | "=" => (Matcher::Equals(value), false),
+            "!=" => (Matcher::Equals(value), true),
+            "=~" | "!~" => (
+                Matcher::Regex(Regex::new(&value).with_context(|| format!("Invalid regex '{}'", value))?),
+                op == "!~",
+            ),
+            "like" => (
+                Matcher::Glob(Glob::new(&value).with_context(|| format!("Invalid glob '{}'", value))?.compile_matcher()),
+                false,
+            ),
+            "<" | "<=" | ">" | ">=" => {
+                let ordering = if op.starts_with('<') { std::cmp::Ordering::Less } else { std::cmp::Ordering::Greater };
+                let matcher = match field {
+                    Field::Size => {
+                        let bytes = parse_size(&value).with_context(|| format!("Invalid size '{}'", value))?;
+                        Matcher::Compare(ordering, op.ends_with('='), bytes)
+                    }
+                    Field::Status => {
+                        let level = CompilerFilter::parse(&value).with_context(|| {
+                            format!("Unknown compiler filter '{}' (expected one of: {})", value, CompilerFilter::names())
+                        })?;
+                        Matcher::Level(ordering, op.ends_with('='), level)
+                    }
+                    _ => bail!("'{}' is only supported for size and status", op),
+                };
+                (matcher, false)
+            }
+            _ => bail!("Unsupported operator '{}'", op),
+        };
+
+        Ok(Expr::Test { field, matcher, negate })
+    }
+}
+
+/// A parsed `--where` expression and its source text.
+#[derive(Debug)]
+pub struct Query {
+    pub source: String,
+    expr: Expr,
+}
+
+impl Query {
+    pub fn new(source: &str) -> Result<Self> {
+        Ok(Query { source: source.to_string(), expr: parse(source)? })
+    }
+
+    /// Whether the expression refers to the installer or origin, which are fetched separately.
+    pub fn needs_origins(&self) -> bool {
+        self.expr.uses(Field::Installer) || self.expr.uses(Field::Origin)
+    }
+
+    pub fn matches(&self, package: &Package, infos: Option<&Vec<DexOptInfo>>, origins: Option<&Origins>) -> bool {
+        let installer = origins.and_then(|o| o.installer(package));
+        let origin = origins.map(|o| o.origin(package));
+        self.expr.matches(&Subject::new(package, infos, installer, origin))
+    }
+}
+
+/// Parses a `--where` expression.
+pub fn parse(input: &str) -> Result<Expr> {
+    let mut parser = Parser { tokens: tokenize(input)?, pos: 0 };
+    let expr = parser.or()?;
+    if let Some(token) = parser.peek() {
+        bail!("Unexpected {} in --where expression", describe(Some(token)));
+    }
+    Ok(expr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(isa: &str, status: &str, reason: Option<&str>) -> DexOptInfo {
+        DexOptInfo {
+            raw_line: String::new(),
+            isa: isa.into(),
+            status: status.into(),
+            reason: reason.map(str::to_string),
+            primary_abi: isa == "arm64",
+            dex_path: None,
+            location: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("status ==").is_err());
+        assert!(parse("colour == red").is_err());
+        assert!(parse("reason > install").is_err());
+        assert!(parse("status > fast").is_err());
+        assert!(parse("(name == a").is_err());
+        assert!(parse("name == &").is_err());
+        assert!(parse("name =~ '['").is_err());
+        assert_eq!(parse_size("1.5M"), Some(1_572_864));
+    }
+
+    #[test]
+    fn test_evaluation() {
+        let pkg = Package { name: "com.google.maps".into(), path: String::new() };
+        let infos = vec![info("arm64", "speed-profile", Some("bg-dexopt")), info("arm", "verify", Some("install"))];
+        let subject = Subject::new(&pkg, Some(&infos), Some("com.android.vending"), Some(Origin::UpdatedSystem));
+        let check = |q: &str| parse(q).unwrap().matches(&subject);
+
+        assert!(check("name like 'com.google.*' and status == verify"));
+        // Both conditions must hold for the same entry
+        assert!(!check("isa == arm64 and status == verify"));
+        assert!(check("isa == arm && (status == verify || reason == bg-dexopt)"));
+        assert!(check(r#"name =~ "^com\.google\." and not installer != com.android.vending"#));
+        assert!(!check("reason == cmdline or name !~ google"));
+        assert!(check("origin == updated-system"));
+        assert!(!check("size > 1K"));
+        assert!(check("isa == arm64 and status >= speed-profile and status < speed"));
+        assert!(!check("status > speed-profile"));
+    }
+}
@@ -0,0 +1,49 @@
+/// Compilation reasons reported in `reason=`, with what triggered them.
+///
+/// Names follow ART's `ReasonMapping` and the older `PackageManagerServiceCompilerMapping`.
+pub const REASONS: &[(&str, &str)] = &[
+    ("first-boot", "First boot of the device; compiled with a fast filter to keep boot short"),
+    ("boot-after-ota", "First boot after a system update"),
+    ("boot-after-mainline-update", "First boot after an ART mainline module update"),
+    ("post-boot", "Compiled shortly after boot (older releases)"),
+    ("install", "Compiled when the app was installed or updated"),
+    ("install-fast", "Fast compilation during install (older releases)"),
+    ("install-bulk", "Installed as part of a batch, e.g. a restore"),
+    ("install-bulk-secondary", "Batch install of an app for a secondary user"),
+    ("install-bulk-downgraded", "Batch install of a rarely used app, compiled with a lighter filter"),
+    ("install-bulk-secondary-downgraded", "Batch install for a secondary user of a rarely used app, with a lighter filter"),
+    ("install-dm", "Installed together with a dex metadata (.dm) file, usually carrying a cloud profile"),
+    ("cloud", "Compiled with a cloud profile delivered by the app store"),
+    ("bg-dexopt", "Background dexopt job during idle maintenance, guided by the app's profile"),
+    ("inactive", "Unused for a long time, so the background job downgraded it to save space"),
+    ("ab-ota", "Compiled ahead of an A/B system update (otapreopt)"),
+    ("cmdline", "Requested manually, e.g. `cmd package compile` or `pm art`"),
+    ("shared", "Code loaded by other apps, compiled without a profile"),
+    ("prebuilt", "Artifacts shipped with the system image"),
+    ("vdex", "Only the verification data (vdex) of an earlier compilation is left"),
+    ("unknown", "ART did not record why the artifacts were produced"),
+];
+
+/// Short explanation of `reason`, if it is a known one.
+pub fn describe(reason: &str) -> Option<&'static str> {
+    REASONS.iter().find(|(name, _)| *name == reason).map(|(_, description)| *description)
+}
+
+/// Key under which entries without a `reason=` are counted.
+pub const NONE: &str = "none";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe() {
+        assert!(describe("bg-dexopt").unwrap().contains("Background"));
+        assert!(describe("install-dm").is_some());
+        assert_eq!(describe("bogus"), None);
+        let mut names: Vec<&str> = REASONS.iter().map(|(name, _)| *name).collect();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), REASONS.len());
+    }
+}
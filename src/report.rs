@@ -0,0 +1,370 @@
+use crate::compiler_filter::CompilerFilter;
+use crate::device::{DeviceProps, DeviceReport};
+use crate::explain::Finding;
+use crate::freshness::{Compilation, Freshness};
+use crate::origin::Origin;
+use crate::reason;
+use crate::sorting::GroupBy;
+use crate::{AppType, DexOptInfo, Filters, Package};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Version of the JSON output, bumped whenever a field is renamed, removed or changes meaning.
+///
+/// The matching JSON Schema lives in `schema/report.schema.json`.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// JSON Schema describing [`Report`] and the NDJSON records.
+pub const SCHEMA: &str = include_str!("../schema/report.schema.json");
+
+/// The document printed by `--format json`.
+#[derive(Debug, Serialize)]
+pub struct Report<'a> {
+    pub schema_version: u32,
+    pub metadata: RunMetadata,
+    pub summary: Summary,
+    #[serde(flatten)]
+    pub listing: Listing<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device: Option<DeviceReport>,
+}
+
+impl<'a> Report<'a> {
+    /// Every package record in listing order, whether grouped or not.
+    pub fn packages(&self) -> impl Iterator<Item = &PackageRecord<'a>> {
+        let (flat, groups): (&[PackageRecord], &[Group]) = match self.listing {
+            Listing::Flat { ref packages } => (packages, &[]),
+            Listing::Grouped { ref groups, .. } => (&[], groups),
+        };
+        flat.iter().chain(groups.iter().flat_map(|g| &g.packages))
+    }
+}
+
+/// The package records of a report: a flat `packages` array, or `groups` with `--group-by`.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum Listing<'a> {
+    Flat { packages: Vec<PackageRecord<'a>> },
+    Grouped { group_by: GroupBy, groups: Vec<Group<'a>> },
+}
+
+impl<'a> Listing<'a> {
+    /// Splits records, already sorted by group, into groups.
+    pub fn grouped(group_by: GroupBy, members: impl IntoIterator<Item = (String, PackageRecord<'a>)>) -> Self {
+        let mut groups: Vec<Group> = Vec::new();
+        for (key, record) in members {
+            match groups.last_mut() {
+                Some(group) if group.key == key => {
+                    group.count += 1;
+                    group.packages.push(record);
+                }
+                _ => groups.push(Group { key, count: 1, packages: vec![record] }),
+            }
+        }
+        Listing::Grouped { group_by, groups }
+    }
+}
+
+/// A `--group-by` group: its key, size and package records, in listing order.
+#[derive(Debug, Serialize)]
+pub struct Group<'a> {
+    pub key: String,
+    pub count: usize,
+    pub packages: Vec<PackageRecord<'a>>,
+}
+
+/// When and where the report was produced, and what it was restricted to.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunMetadata {
+    /// RFC 3339 timestamp in UTC.
+    pub generated_at: String,
+    pub tool_version: &'static str,
+    pub device_model: Option<String>,
+    pub sdk: Option<String>,
+    pub app_type: String,
+    pub filters: AppliedFilters,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AppliedFilters {
+    pub name: Option<String>,
+    pub status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_status: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_status: Option<&'static str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub origin: Vec<Origin>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub installer: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub stale: bool,
+    #[serde(rename = "where", skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+}
+
+impl RunMetadata {
+    pub fn collect(app_type: AppType, filters: &Filters, props: &DeviceProps) -> Self {
+        RunMetadata {
+            generated_at: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            tool_version: env!("CARGO_PKG_VERSION"),
+            device_model: props.get("ro.product.model").map(str::to_string),
+            sdk: props.get("ro.build.version.sdk").map(str::to_string),
+            app_type: app_type.to_string(),
+            filters: AppliedFilters {
+                name: filters.name.clone(),
+                status: filters.status.clone(),
+                min_status: filters.min_status.map(CompilerFilter::as_str),
+                max_status: filters.max_status.map(CompilerFilter::as_str),
+                origin: filters.origin.clone(),
+                installer: filters.installer.clone(),
+                stale: filters.stale,
+                query: filters.query.as_ref().map(|q| q.source.clone()),
+            },
+        }
+    }
+}
+
+/// Package and per-status counts, as shown by the text summary.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Summary {
+    pub total_apps: usize,
+    /// Packages with an artifact older than its APK.
+    pub stale: usize,
+    /// Packages whose artifacts vanished although the dump reports a compiler filter.
+    pub missing_artifacts: usize,
+    pub statuses: BTreeMap<String, usize>,
+    /// Dexopt entries per reason (`none` when the dump gives none).
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub reasons: BTreeMap<String, usize>,
+    /// Dexopt entries per status, then per reason.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub status_reasons: BTreeMap<String, BTreeMap<String, usize>>,
+    /// Packages per origin, when origins were looked up.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub origins: BTreeMap<String, usize>,
+    /// Packages per installer (`none` for preinstalled and sideloaded apps).
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub installers: BTreeMap<String, usize>,
+}
+
+impl Summary {
+    /// Counts packages with dexopt info and dexopt entries per status.
+    pub fn from_records(records: &[PackageRecord]) -> Self {
+        let mut summary = Summary::default();
+        for record in records.iter().filter(|r| !r.dexopt.is_empty()) {
+            summary.total_apps += 1;
+            for entry in &record.dexopt {
+                summary.count_entry(entry.status, entry.reason);
+            }
+            if let Some(freshness) = record.freshness {
+                summary.count_freshness(freshness);
+            }
+            if let Some(origin) = record.origin {
+                summary.count_origin(origin, record.installer.as_deref());
+            }
+        }
+        summary
+    }
+
+    pub fn count_entry(&mut self, status: &str, reason: Option<&str>) {
+        let reason = reason.unwrap_or(reason::NONE);
+        *self.statuses.entry(status.to_string()).or_insert(0) += 1;
+        *self.reasons.entry(reason.to_string()).or_insert(0) += 1;
+        *self
+            .status_reasons
+            .entry(status.to_string())
+            .or_default()
+            .entry(reason.to_string())
+            .or_insert(0) += 1;
+    }
+
+    pub fn count_freshness(&mut self, freshness: Freshness) {
+        match freshness {
+            Freshness::Fresh => {}
+            Freshness::Stale => self.stale += 1,
+            Freshness::Missing => self.missing_artifacts += 1,
+        }
+    }
+
+    pub fn count_origin(&mut self, origin: Origin, installer: Option<&str>) {
+        *self.origins.entry(origin.to_string()).or_insert(0) += 1;
+        *self.installers.entry(installer.unwrap_or("none").to_string()).or_insert(0) += 1;
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PackageRecord<'a> {
+    pub package: &'a str,
+    pub label: Option<String>,
+    pub path: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub origin: Option<Origin>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub installer: Option<String>,
+    /// RFC 3339 time of the newest artifact.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_dexopt: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub freshness: Option<Freshness>,
+    pub dexopt: Vec<DexoptEntry<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diagnosis: Option<Vec<Finding>>,
+}
+
+impl<'a> PackageRecord<'a> {
+    pub fn new(pkg: &'a Package, label: Option<String>, infos: Option<&'a Vec<DexOptInfo>>) -> Self {
+        PackageRecord {
+            package: &pkg.name,
+            label,
+            path: &pkg.path,
+            origin: None,
+            installer: None,
+            last_dexopt: None,
+            freshness: None,
+            dexopt: infos.into_iter().flatten().map(|info| DexoptEntry::new(info, pkg)).collect(),
+            diagnosis: None,
+        }
+    }
+}
+
+impl PackageRecord<'_> {
+    pub fn set_compilation(&mut self, compilation: Option<Compilation>) {
+        let Some(compilation) = compilation else {
+            return;
+        };
+        self.last_dexopt = compilation.last_dexopt.map(|t| {
+            chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+        });
+        self.freshness = Some(compilation.freshness);
+    }
+}
+
+/// One ISA line of a dex file, decoupled from the parser's `DexOptInfo`.
+#[derive(Debug, Serialize)]
+pub struct DexoptEntry<'a> {
+    pub isa: &'a str,
+    pub status: &'a str,
+    pub reason: Option<&'a str>,
+    /// What `reason` means, for known reasons.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason_description: Option<&'static str>,
+    pub primary_abi: bool,
+    pub dex_path: Option<&'a str>,
+    pub artifact: Option<&'a str>,
+    /// Combined size of the `.odex`, `.vdex` and `.art` files.
+    pub artifact_bytes: Option<u64>,
+    /// The line as printed by `dumpsys package dexopt`.
+    pub raw: &'a str,
+}
+
+impl<'a> DexoptEntry<'a> {
+    fn new(info: &'a DexOptInfo, pkg: &Package) -> Self {
+        DexoptEntry {
+            isa: &info.isa,
+            status: &info.status,
+            reason: info.reason.as_deref(),
+            reason_description: info.reason.as_deref().and_then(reason::describe),
+            primary_abi: info.primary_abi,
+            dex_path: info.dex_path.as_deref(),
+            artifact: info.location.as_deref(),
+            artifact_bytes: info.artifact_bytes(pkg),
+            raw: &info.raw_line,
+        }
+    }
+}
+
+/// A line of `--format ndjson`: every package as it is processed, then one summary.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Record<'a> {
+    Package(&'a PackageRecord<'a>),
+    Summary(Box<SummaryRecord>),
+}
+
+#[derive(Debug, Serialize)]
+pub struct SummaryRecord {
+    pub schema_version: u32,
+    pub metadata: RunMetadata,
+    pub summary: Summary,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device: Option<DeviceReport>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    /// Every emitted key must be declared by the published schema.
+    fn assert_declared(value: &Value, schema: &Value, defs: &Value) {
+        let schema = match schema.get("$ref").and_then(Value::as_str) {
+            Some(r) => &defs[r.trim_start_matches("#/$defs/")],
+            None => schema,
+        };
+        match value {
+            Value::Object(map) if schema.get("properties").is_some() => {
+                for (key, v) in map {
+                    let prop = &schema["properties"][key];
+                    assert!(!prop.is_null(), "'{}' is missing from the schema", key);
+                    assert_declared(v, prop, defs);
+                }
+            }
+            Value::Array(items) => items.iter().for_each(|v| assert_declared(v, &schema["items"], defs)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_report_matches_schema() {
+        let schema: Value = serde_json::from_str(SCHEMA).unwrap();
+        assert_eq!(schema["properties"]["schema_version"]["const"], SCHEMA_VERSION);
+        assert_eq!(schema["$defs"]["summary_record"]["properties"]["schema_version"]["const"], SCHEMA_VERSION);
+
+        let pkg = Package { name: "com.example.app".into(), path: "/data/app/base.apk".into() };
+        let infos = vec![DexOptInfo {
+            raw_line: "arm64: [status=speed-profile] [reason=bg-dexopt] [primary-abi]".into(),
+            isa: "arm64".into(),
+            status: "speed-profile".into(),
+            reason: Some("bg-dexopt".into()),
+            primary_abi: true,
+            dex_path: Some("/data/app/base.apk".into()),
+            location: None,
+        }];
+        let filters = Filters { status: Some("speed-profile".into()), stale: true, ..Filters::default() };
+        let record = || {
+            let mut record = PackageRecord::new(&pkg, None, Some(&infos));
+            record.origin = Some(Origin::Data);
+            record.installer = Some("com.android.vending".into());
+            record.set_compilation(Some(Compilation { last_dexopt: Some(std::time::SystemTime::now()), freshness: Freshness::Stale }));
+            record
+        };
+        let listings = [
+            Listing::Flat { packages: vec![record()] },
+            Listing::grouped(GroupBy::Status, [("speed-profile".to_string(), record()), ("speed-profile".to_string(), record())]),
+        ];
+
+        for (listing, (key, count)) in listings.into_iter().zip([("packages", 1), ("groups", 2)]) {
+            let report = Report {
+                schema_version: SCHEMA_VERSION,
+                metadata: RunMetadata::collect(AppType::User, &filters, &DeviceProps::default()),
+                summary: Summary::default(),
+                listing,
+                device: None,
+            };
+            assert_eq!(report.packages().count(), count);
+
+            let value = serde_json::to_value(&report).unwrap();
+            for required in schema["required"].as_array().unwrap() {
+                assert!(value.get(required.as_str().unwrap()).is_some());
+            }
+            // Exactly one of the listing shapes
+            let shape = schema["oneOf"].as_array().unwrap().iter().filter(|s| {
+                s["required"].as_array().unwrap().iter().all(|k| value.get(k.as_str().unwrap()).is_some())
+            });
+            assert_eq!(shape.count(), 1);
+            assert!(value.get(key).is_some());
+            assert_declared(&value, &schema, &schema["$defs"]);
+        }
+    }
+}
@@ -0,0 +1,320 @@
+use crate::bg_dexopt::{self, JobRun};
+use crate::device::DeviceProps;
+use crate::report::{self, Listing, PackageRecord, Report, RunMetadata, Summary, SummaryRecord};
+use crate::{Analyzer, AppType, Filters, Package, PackageAction, bg_dexopt_last_run, metrics};
+use anyhow::{Result, anyhow};
+use colored::*;
+use serde::Serialize;
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// How long a package list and dexopt dump are reused before the device is queried again.
+const CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// Requests handled at once per server; a slow optimize ties up one worker, not the others.
+const WORKERS: usize = 4;
+
+type HttpResponse = Response<std::io::Cursor<Vec<u8>>>;
+type ApiResult = std::result::Result<serde_json::Value, (u16, String)>;
+
+/// Package list and parsed dexopt dump, fetched together.
+struct Snapshot {
+    packages: Vec<Package>,
+    analyzer: Analyzer,
+    /// Background dexopt runs recorded in the same dump.
+    runs: Vec<JobRun>,
+}
+
+struct State {
+    app_type: AppType,
+    filters: Filters,
+    cache: Mutex<Option<(Instant, Arc<Snapshot>)>>,
+}
+
+impl State {
+    /// Returns the cached snapshot, refreshing it once it is older than `CACHE_TTL`.
+    fn snapshot(&self) -> Result<Arc<Snapshot>> {
+        let mut cache = self.cache.lock().map_err(|_| anyhow!("Snapshot cache is poisoned"))?;
+        if let Some((fetched, ref snapshot)) = *cache
+            && fetched.elapsed() < CACHE_TTL
+        {
+            return Ok(Arc::clone(snapshot));
+        }
+
+        let dump = Analyzer::fetch_dump()?;
+        let snapshot = Arc::new(Snapshot {
+            packages: Package::fetch_list(self.app_type)?,
+            analyzer: Analyzer::new(&dump),
+            runs: bg_dexopt::parse_job_runs(&dump),
+        });
+        *cache = Some((Instant::now(), Arc::clone(&snapshot)));
+        Ok(snapshot)
+    }
+
+    fn invalidate(&self) {
+        if let Ok(mut cache) = self.cache.lock() {
+            *cache = None;
+        }
+    }
+}
+
+fn bind(addr: &str) -> Result<Server> {
+    Server::http(addr).map_err(|e| anyhow!("Failed to listen on '{}': {}", addr, e))
+}
+
+/// Serves the JSON API on `listen` and/or Prometheus metrics on `metrics_addr` until the process is killed.
+pub fn run(listen: Option<&str>, metrics_addr: Option<&str>, app_type: AppType, filters: Filters) -> Result<()> {
+    let state = Arc::new(State {
+        app_type,
+        filters,
+        cache: Mutex::new(None),
+    });
+
+    let api_server = listen.map(bind).transpose()?;
+    let metrics_server = metrics_addr.map(bind).transpose()?;
+
+    if let Some(addr) = listen {
+        eprintln!("{} Serving the API on http://{}/", "[-]".cyan(), addr);
+    }
+    if let Some(addr) = metrics_addr {
+        eprintln!("{} Serving metrics on http://{}/metrics", "[-]".cyan(), addr);
+    }
+
+    match (api_server, metrics_server) {
+        (Some(api), Some(metrics)) => {
+            let metrics_state = Arc::clone(&state);
+            thread::spawn(move || serve(metrics, metrics_state, true));
+            serve(api, state, false);
+        }
+        (Some(api), None) => serve(api, state, false),
+        (None, Some(metrics)) => serve(metrics, state, true),
+        (None, None) => {}
+    }
+
+    Ok(())
+}
+
+/// Handles requests on a fixed pool of `WORKERS` threads that share the listener.
+fn serve(server: Server, state: Arc<State>, metrics_only: bool) {
+    let server = Arc::new(server);
+    let workers: Vec<_> = (0..WORKERS)
+        .map(|_| {
+            let server = Arc::clone(&server);
+            let state = Arc::clone(&state);
+            thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    let response = route(&request, &state, metrics_only);
+                    // The client may have gone away; that is not our problem
+                    let _ = request.respond(response);
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        let _ = worker.join();
+    }
+}
+
+/// Decodes `%XX` escapes, and `+` as a space in query strings.
+fn percent_decode(text: &str, plus_as_space: bool) -> std::result::Result<String, (u16, String)> {
+    let invalid = || (400, format!("Invalid percent-encoding in '{}'", text));
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = text.get(i + 1..i + 3).filter(|h| h.bytes().all(|b| b.is_ascii_hexdigit())).ok_or_else(invalid)?;
+                out.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+                i += 3;
+            }
+            b'+' if plus_as_space => {
+                out.push(b' ');
+                i += 1;
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(out).map_err(|_| invalid())
+}
+
+fn header_value<'a>(request: &'a Request, name: &'static str) -> Option<&'a str> {
+    request.headers().iter().find(|h| h.field.equiv(name)).map(|h| h.value.as_str())
+}
+
+/// Rejects state-changing requests a web page could forge: an HTML form cannot send a JSON
+/// content type, and a cross-site `fetch` with one carries a foreign `Origin`.
+fn check_same_origin(request: &Request) -> std::result::Result<(), (u16, String)> {
+    let json = header_value(request, "Content-Type")
+        .is_some_and(|v| v.split(';').next().is_some_and(|t| t.trim().eq_ignore_ascii_case("application/json")));
+    if !json {
+        return Err((415, "Expected 'Content-Type: application/json'".to_string()));
+    }
+    if let Some(origin) = header_value(request, "Origin") {
+        let origin_host = origin.split_once("://").map_or(origin, |(_, host)| host);
+        if header_value(request, "Host") != Some(origin_host) {
+            return Err((403, format!("Cross-origin request from '{}' rejected", origin)));
+        }
+    }
+    Ok(())
+}
+
+fn route(request: &Request, state: &State, metrics_only: bool) -> HttpResponse {
+    let (path, query) = request.url().split_once('?').unwrap_or((request.url(), ""));
+    let segments = match path
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(|s| percent_decode(s, false))
+        .collect::<std::result::Result<Vec<String>, _>>()
+    {
+        Ok(segments) => segments,
+        Err((code, message)) => return json_response(code, &json!({ "error": message })),
+    };
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+
+    let result = match (request.method(), segments.as_slice()) {
+        (Method::Get, ["metrics"]) => {
+            return match scrape(state) {
+                Ok(body) => Response::from_string(body).with_header(header("Content-Type", metrics::CONTENT_TYPE)),
+                Err(e) => Response::from_string(format!("{:#}\n", e)).with_status_code(500),
+            };
+        }
+        _ if metrics_only => Err((404, "Not found".to_string())),
+        (Method::Get, ["packages"]) => list_packages(state, query),
+        (Method::Get, ["packages", name]) => get_package(state, name),
+        (Method::Get, ["summary"]) => get_summary(state, query),
+        (Method::Post, ["packages", name, "optimize"]) => check_same_origin(request).and_then(|()| optimize(state, name)),
+        (_, ["packages"] | ["packages", _] | ["summary"] | ["packages", _, "optimize"]) => {
+            Err((405, "Method not allowed".to_string()))
+        }
+        _ => Err((404, "Not found".to_string())),
+    };
+
+    match result {
+        Ok(body) => json_response(200, &body),
+        Err((code, message)) => json_response(code, &json!({ "error": message })),
+    }
+}
+
+fn internal(e: anyhow::Error) -> (u16, String) {
+    (500, format!("{:#}", e))
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name, value).expect("Invalid header")
+}
+
+fn json_response(code: u16, body: &impl Serialize) -> HttpResponse {
+    let body = serde_json::to_string_pretty(body).unwrap_or_default();
+    Response::from_string(body)
+        .with_status_code(code)
+        .with_header(header("Content-Type", "application/json"))
+}
+
+/// `?filter=` and `?status=` replace the filters given on the command line.
+fn query_filters(state: &State, query: &str) -> std::result::Result<Filters, (u16, String)> {
+    let mut filters = state.filters.clone();
+    for (key, value) in query.split('&').filter_map(|p| p.split_once('=')) {
+        match key {
+            "filter" => filters.name = Some(percent_decode(value, true)?),
+            "status" => filters.status = Some(percent_decode(value, true)?),
+            _ => {}
+        }
+    }
+    Ok(filters)
+}
+
+fn records<'a>(snapshot: &'a Snapshot, filters: &Filters) -> Vec<PackageRecord<'a>> {
+    snapshot
+        .packages
+        .iter()
+        .filter(|pkg| filters.matches(pkg, snapshot.analyzer.get_info(&pkg.name)))
+        .map(|pkg| PackageRecord::new(pkg, None, snapshot.analyzer.get_info(&pkg.name)))
+        .collect()
+}
+
+fn find<'a>(snapshot: &'a Snapshot, name: &str) -> std::result::Result<&'a Package, (u16, String)> {
+    snapshot
+        .packages
+        .iter()
+        .find(|p| p.name == name)
+        .ok_or_else(|| (404, format!("Package '{}' not found", name)))
+}
+
+fn list_packages(state: &State, query: &str) -> ApiResult {
+    let snapshot = state.snapshot().map_err(internal)?;
+    Ok(json!(records(&snapshot, &query_filters(state, query)?)))
+}
+
+fn get_package(state: &State, name: &str) -> ApiResult {
+    let snapshot = state.snapshot().map_err(internal)?;
+    let pkg = find(&snapshot, name)?;
+    Ok(json!(PackageRecord::new(pkg, pkg.get_label(), snapshot.analyzer.get_info(&pkg.name))))
+}
+
+fn get_summary(state: &State, query: &str) -> ApiResult {
+    let filters = query_filters(state, query)?;
+    let snapshot = state.snapshot().map_err(internal)?;
+    let records = records(&snapshot, &filters);
+    Ok(json!(SummaryRecord {
+        schema_version: report::SCHEMA_VERSION,
+        metadata: RunMetadata::collect(state.app_type, &filters, &DeviceProps::fetch().unwrap_or_default()),
+        summary: Summary::from_records(&records),
+        device: None,
+    }))
+}
+
+/// Runs the same steps as `dexter -o <name>` and returns the package's dexopt state afterwards.
+fn optimize(state: &State, name: &str) -> ApiResult {
+    find(&*state.snapshot().map_err(internal)?, name)?;
+
+    let failures = PackageAction::Optimize.run(name).map_err(internal)?;
+    state.invalidate();
+
+    let snapshot = state.snapshot().map_err(internal)?;
+    let pkg = find(&snapshot, name)?;
+    Ok(json!({
+        "package": name,
+        "success": failures.is_empty(),
+        "failures": failures,
+        "dexopt": PackageRecord::new(pkg, None, snapshot.analyzer.get_info(&pkg.name)).dexopt,
+    }))
+}
+
+fn scrape(state: &State) -> Result<String> {
+    let snapshot = state.snapshot()?;
+    let records = records(&snapshot, &state.filters);
+
+    let report = Report {
+        schema_version: report::SCHEMA_VERSION,
+        metadata: RunMetadata::collect(state.app_type, &state.filters, &DeviceProps::fetch().unwrap_or_default()),
+        summary: Summary::from_records(&records),
+        listing: Listing::Flat { packages: records },
+        device: None,
+    };
+
+    Ok(metrics::render(&report, bg_dexopt_last_run(&snapshot.runs, &snapshot.packages, &snapshot.analyzer)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("com.example%2Eapp", false).unwrap(), "com.example.app");
+        assert_eq!(percent_decode("speed%2Dprofile+x", true).unwrap(), "speed-profile x");
+        assert_eq!(percent_decode("a+b", false).unwrap(), "a+b");
+        assert_eq!(percent_decode("%E6%97%A5", false).unwrap(), "日");
+        assert_eq!(percent_decode("%zz", false).unwrap_err().0, 400);
+        assert_eq!(percent_decode("%+1", false).unwrap_err().0, 400);
+        assert_eq!(percent_decode("%ff", false).unwrap_err().0, 400);
+        assert_eq!(percent_decode("50%", false).unwrap_err().0, 400);
+    }
+}
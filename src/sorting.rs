@@ -0,0 +1,162 @@
+use crate::compiler_filter::{self, CompilerFilter};
+use crate::origin::Origins;
+use crate::{Analyzer, DexOptInfo, Package};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs;
+use std::time::SystemTime;
+
+/// Order of the package listing.
+#[derive(Copy, Clone, PartialEq, Eq, Default, ValueEnum, Debug)]
+pub enum SortKey {
+    #[default]
+    Name,
+    Label,
+    Status,
+    Reason,
+    Size,
+    InstallTime,
+}
+
+/// What the package listing is grouped by.
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum GroupBy {
+    Status,
+    Reason,
+    Installer,
+    Partition,
+}
+
+/// Per-package data that sorting and grouping need beyond the dexopt dump.
+pub struct Keys<'a> {
+    pub analyzer: &'a Analyzer,
+    /// Labels, only fetched when sorting by label.
+    pub labels: HashMap<&'a str, Option<String>>,
+    pub origins: &'a Origins,
+}
+
+/// The primary ABI entry, or the first entry when none is marked.
+fn primary(infos: &[DexOptInfo]) -> Option<&DexOptInfo> {
+    infos.iter().find(|i| i.primary_abi).or(infos.first())
+}
+
+/// Sort key of a package's primary status; `run-from-apk` and `error` come before every compiler filter.
+pub fn status_level(infos: &[DexOptInfo]) -> Option<(Option<CompilerFilter>, &str)> {
+    primary(infos).map(|i| compiler_filter::status_order(&i.status))
+}
+
+fn primary_reason(infos: &[DexOptInfo]) -> Option<&str> {
+    primary(infos).and_then(|i| i.reason.as_deref())
+}
+
+fn install_time(pkg: &Package) -> Option<SystemTime> {
+    fs::metadata(&pkg.path).ok()?.modified().ok()
+}
+
+/// A package's sort or group key, computed once per package since some keys hit the filesystem.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum Value<'a> {
+    None,
+    Text(String),
+    Status(Option<(Option<CompilerFilter>, &'a str)>),
+    Reason(Option<&'a str>),
+    // Largest and most recent first
+    Size(Reverse<Option<u64>>),
+    Time(Reverse<Option<SystemTime>>),
+}
+
+impl<'a> Keys<'a> {
+    fn infos(&self, pkg: &Package) -> &'a [DexOptInfo] {
+        self.analyzer.get_info(&pkg.name).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    fn size(&self, pkg: &Package) -> Option<u64> {
+        self.infos(pkg).iter().filter_map(|i| i.artifact_bytes(pkg)).reduce(|a, b| a + b)
+    }
+
+    /// Group a package belongs to, as shown in the group header.
+    pub fn group(&self, pkg: &Package, group_by: GroupBy) -> String {
+        let infos = self.infos(pkg);
+        match group_by {
+            GroupBy::Status => status_level(infos).map_or("unknown", |(_, s)| s).to_string(),
+            GroupBy::Reason => primary_reason(infos).unwrap_or("unknown").to_string(),
+            GroupBy::Installer => self.origins.installer(pkg).unwrap_or("none").to_string(),
+            GroupBy::Partition => self.origins.origin(pkg).to_string(),
+        }
+    }
+
+    fn group_value(&self, pkg: &Package, group_by: GroupBy) -> Value<'a> {
+        match group_by {
+            // Groups follow the optimization level rather than the alphabet
+            GroupBy::Status => Value::Status(status_level(self.infos(pkg))),
+            _ => Value::Text(self.group(pkg, group_by)),
+        }
+    }
+
+    fn sort_value(&self, pkg: &Package, key: SortKey) -> Value<'a> {
+        match key {
+            SortKey::Name => Value::None,
+            SortKey::Label => Value::Text(
+                self.labels.get(pkg.name.as_str()).cloned().flatten().unwrap_or_else(|| pkg.name.clone()).to_lowercase(),
+            ),
+            SortKey::Status => Value::Status(status_level(self.infos(pkg))),
+            SortKey::Reason => Value::Reason(primary_reason(self.infos(pkg))),
+            SortKey::Size => Value::Size(Reverse(self.size(pkg))),
+            SortKey::InstallTime => Value::Time(Reverse(install_time(pkg))),
+        }
+    }
+
+    /// Sorts `packages` by group first (so each group is contiguous), then by `key`.
+    pub fn sort<'p>(&self, packages: &mut [&'p Package], key: SortKey, group_by: Option<GroupBy>) {
+        packages.sort_by_cached_key(|&pkg: &&'p Package| {
+            (group_by.map(|g| self.group_value(pkg, g)), self.sort_value(pkg, key), &pkg.name)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(isa: &str, status: &str, reason: &str) -> DexOptInfo {
+        DexOptInfo {
+            raw_line: String::new(),
+            isa: isa.into(),
+            status: status.into(),
+            reason: Some(reason.into()),
+            primary_abi: isa == "arm64",
+            dex_path: None,
+            location: None,
+        }
+    }
+
+    #[test]
+    fn test_sort_within_groups() {
+        let dump = "[com.b]\n  path: /data/app/b/base.apk\n    arm64: [status=verify] [reason=install]\n\
+                    [com.a]\n  path: /data/app/a/base.apk\n    arm64: [status=speed-profile] [reason=bg-dexopt]\n\
+                    [com.c]\n  path: /data/app/c/base.apk\n    arm64: [status=error] [reason=install]\n";
+        let analyzer = Analyzer::new(dump);
+        let origins = Origins::default();
+        let keys = Keys { analyzer: &analyzer, labels: HashMap::new(), origins: &origins };
+        let packages: Vec<Package> = ["com.a", "com.b", "com.c"]
+            .iter()
+            .map(|n| Package { name: n.to_string(), path: format!("/data/app/{}/base.apk", n) })
+            .collect();
+
+        let mut sorted: Vec<&Package> = packages.iter().collect();
+        keys.sort(&mut sorted, SortKey::Status, None);
+        let names: Vec<&str> = sorted.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["com.c", "com.b", "com.a"]);
+
+        keys.sort(&mut sorted, SortKey::Name, Some(GroupBy::Reason));
+        let grouped: Vec<(String, &str)> = sorted.iter().map(|p| (keys.group(p, GroupBy::Reason), p.name.as_str())).collect();
+        assert_eq!(
+            grouped,
+            [("bg-dexopt".to_string(), "com.a"), ("install".to_string(), "com.b"), ("install".to_string(), "com.c")]
+        );
+        assert_eq!(primary_reason(&[info("arm", "verify", "a"), info("arm64", "speed", "b")]), Some("b"));
+    }
+}
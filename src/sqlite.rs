@@ -0,0 +1,193 @@
+use crate::ApkDetails;
+use crate::device::DeviceProps;
+use crate::report::Report;
+use anyhow::{Context, Result};
+use rusqlite::{Connection, params};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Bumped together with a migration whenever the tables below change.
+const SCHEMA_VERSION: i32 = 1;
+
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    generated_at TEXT NOT NULL,
+    tool_version TEXT NOT NULL,
+    device_serial TEXT,
+    device_model TEXT,
+    build_fingerprint TEXT,
+    sdk INTEGER,
+    app_type TEXT NOT NULL,
+    filter_name TEXT,
+    filter_status TEXT,
+    total_apps INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS packages (
+    id INTEGER PRIMARY KEY,
+    run_id INTEGER NOT NULL REFERENCES runs(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    label TEXT,
+    path TEXT NOT NULL,
+    UNIQUE (run_id, name)
+);
+CREATE TABLE IF NOT EXISTS apk_metadata (
+    package_id INTEGER PRIMARY KEY REFERENCES packages(id) ON DELETE CASCADE,
+    version_name TEXT,
+    version_code TEXT,
+    min_sdk TEXT,
+    target_sdk INTEGER,
+    debuggable INTEGER NOT NULL,
+    vm_safe_mode INTEGER NOT NULL,
+    has_code INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS dexopt_entries (
+    id INTEGER PRIMARY KEY,
+    package_id INTEGER NOT NULL REFERENCES packages(id) ON DELETE CASCADE,
+    isa TEXT NOT NULL,
+    status TEXT NOT NULL,
+    reason TEXT,
+    primary_abi INTEGER NOT NULL,
+    dex_path TEXT,
+    artifact TEXT,
+    artifact_bytes INTEGER,
+    raw TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS packages_name ON packages(name);
+CREATE INDEX IF NOT EXISTS dexopt_entries_package ON dexopt_entries(package_id);
+CREATE INDEX IF NOT EXISTS dexopt_entries_status ON dexopt_entries(status);
+"#;
+
+fn open(path: &Path) -> Result<Connection> {
+    let conn = Connection::open(path).with_context(|| format!("Failed to open database '{}'", path.display()))?;
+    let version: i32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version > SCHEMA_VERSION {
+        anyhow::bail!(
+            "Database '{}' uses schema version {}, but this dexter only knows version {}",
+            path.display(),
+            version,
+            SCHEMA_VERSION
+        );
+    }
+    conn.execute_batch(SCHEMA)?;
+    conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+    conn.pragma_update(None, "foreign_keys", true)?;
+    Ok(conn)
+}
+
+/// Appends the report as a new run; earlier runs are kept for trend queries.
+///
+/// Returns the id of the inserted run.
+pub fn export(path: &Path, report: &Report, apk: &HashMap<&str, ApkDetails>, props: &DeviceProps) -> Result<i64> {
+    let mut conn = open(path)?;
+    let tx = conn.transaction()?;
+    let meta = &report.metadata;
+
+    tx.execute(
+        "INSERT INTO runs (generated_at, tool_version, device_serial, device_model, build_fingerprint, sdk, app_type, filter_name, filter_status, total_apps)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            meta.generated_at,
+            meta.tool_version,
+            props.get("ro.serialno"),
+            meta.device_model,
+            props.get("ro.build.fingerprint"),
+            meta.sdk.as_deref().and_then(|s| s.parse::<i64>().ok()),
+            meta.app_type,
+            meta.filters.name,
+            meta.filters.status,
+            report.summary.total_apps as i64,
+        ],
+    )?;
+    let run_id = tx.last_insert_rowid();
+
+    {
+        let mut insert_package = tx.prepare("INSERT INTO packages (run_id, name, label, path) VALUES (?1, ?2, ?3, ?4)")?;
+        let mut insert_apk = tx.prepare(
+            "INSERT INTO apk_metadata (package_id, version_name, version_code, min_sdk, target_sdk, debuggable, vm_safe_mode, has_code)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        )?;
+        let mut insert_entry = tx.prepare(
+            "INSERT INTO dexopt_entries (package_id, isa, status, reason, primary_abi, dex_path, artifact, artifact_bytes, raw)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        )?;
+
+        for pkg in report.packages() {
+            let package_id = insert_package.insert(params![run_id, pkg.package, pkg.label, pkg.path])?;
+
+            if let Some(details) = apk.get(pkg.package) {
+                insert_apk.execute(params![
+                    package_id,
+                    details.version_name,
+                    details.version_code,
+                    details.min_sdk,
+                    details.target_sdk,
+                    details.flags.debuggable,
+                    details.flags.vm_safe_mode,
+                    details.flags.has_code,
+                ])?;
+            }
+
+            for entry in &pkg.dexopt {
+                insert_entry.execute(params![
+                    package_id,
+                    entry.isa,
+                    entry.status,
+                    entry.reason,
+                    entry.primary_abi,
+                    entry.dex_path,
+                    entry.artifact,
+                    entry.artifact_bytes.map(|b| b as i64),
+                    entry.raw,
+                ])?;
+            }
+        }
+    }
+
+    tx.commit()?;
+    Ok(run_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::{self, Listing, PackageRecord, RunMetadata, Summary};
+    use crate::{AppType, DexOptInfo, Filters, Package};
+
+    #[test]
+    fn test_runs_are_appended() {
+        let path = std::env::temp_dir().join(format!("dexter-test-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let pkg = Package { name: "com.example.app".into(), path: "/data/app/base.apk".into() };
+        let infos = vec![DexOptInfo {
+            raw_line: "arm64: [status=verify] [reason=install]".into(),
+            isa: "arm64".into(),
+            status: "verify".into(),
+            reason: Some("install".into()),
+            primary_abi: true,
+            dex_path: None,
+            location: None,
+        }];
+        let records = vec![PackageRecord::new(&pkg, Some("Example".into()), Some(&infos))];
+        let filters = Filters::default();
+        let props = DeviceProps::parse("[ro.serialno]: [ABC123]\n[ro.build.version.sdk]: [34]\n");
+        let report = Report {
+            schema_version: report::SCHEMA_VERSION,
+            metadata: RunMetadata::collect(AppType::User, &filters, &props),
+            summary: Summary::from_records(&records),
+            listing: Listing::Flat { packages: records },
+            device: None,
+        };
+
+        let first = export(&path, &report, &HashMap::new(), &props).unwrap();
+        let second = export(&path, &report, &HashMap::new(), &props).unwrap();
+        assert_ne!(first, second);
+
+        let conn = Connection::open(&path).unwrap();
+        let count = |sql: &str| conn.query_row(sql, [], |r| r.get::<_, i64>(0)).unwrap();
+        assert_eq!(count("SELECT COUNT(*) FROM runs WHERE device_serial = 'ABC123' AND sdk = 34"), 2);
+        assert_eq!(count("SELECT COUNT(*) FROM dexopt_entries WHERE status = 'verify'"), 2);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,282 @@
+use crate::html::format_bytes;
+use crate::freshness::{Compilation, Freshness};
+use crate::origin::Origin;
+use crate::theme;
+use crate::{DexOptInfo, Package, UI};
+use clap::ValueEnum;
+use colored::*;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Columns of the compact table view.
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum Column {
+    Name,
+    Label,
+    Status,
+    Reason,
+    Isa,
+    Size,
+    Origin,
+    Installer,
+    LastDexopt,
+}
+
+pub const DEFAULT_COLUMNS: &[Column] = &[Column::Name, Column::Status, Column::Reason, Column::Size];
+
+const SEPARATOR: &str = "  ";
+
+/// Columns are never truncated below this width.
+const MIN_WIDTH: usize = 6;
+
+impl Column {
+    fn title(self) -> &'static str {
+        match self {
+            Column::Name => "Package",
+            Column::Label => "Label",
+            Column::Status => "Status",
+            Column::Reason => "Reason",
+            Column::Isa => "ISA",
+            Column::Size => "Size",
+            Column::Origin => "Origin",
+            Column::Installer => "Installer",
+            Column::LastDexopt => "Last Dexopt",
+        }
+    }
+
+    /// Columns that give up width first when the table does not fit.
+    fn is_flexible(self) -> bool {
+        matches!(self, Column::Name | Column::Label | Column::Installer)
+    }
+
+    /// Status and reason get one sub-column per ISA.
+    fn is_per_isa(self) -> bool {
+        matches!(self, Column::Status | Column::Reason)
+    }
+}
+
+/// One package, collapsed to a single table row.
+pub struct Row<'a> {
+    pub pkg: &'a Package,
+    pub label: Option<String>,
+    pub infos: &'a [DexOptInfo],
+    pub size: Option<u64>,
+    pub origin: Origin,
+    pub installer: Option<&'a str>,
+    pub compilation: Option<Compilation>,
+    /// Group header printed above this row, with `--group-by`.
+    pub group: Option<String>,
+    /// Marks the package name, e.g. for status changes in watch mode.
+    pub highlight: bool,
+}
+
+/// A leaf column: a column, or one ISA of a per-ISA column.
+struct Leaf {
+    column: Column,
+    isa: Option<String>,
+    width: usize,
+}
+
+/// Distinct values of an ISA's entries (one per dex file), joined with '/'.
+fn per_isa(infos: &[DexOptInfo], isa: Option<&str>, value: impl Fn(&DexOptInfo) -> Option<&str>) -> String {
+    let mut values: Vec<&str> = Vec::new();
+    for info in infos.iter().filter(|i| isa.is_none_or(|isa| i.isa == isa)) {
+        let v = value(info).unwrap_or("-");
+        if !values.contains(&v) {
+            values.push(v);
+        }
+    }
+    if values.is_empty() { "-".to_string() } else { values.join("/") }
+}
+
+fn cell(row: &Row, leaf: &Leaf) -> String {
+    let isa = leaf.isa.as_deref();
+    match leaf.column {
+        Column::Name => row.pkg.name.clone(),
+        Column::Label => row.label.clone().unwrap_or_default(),
+        Column::Status => per_isa(row.infos, isa, |i| Some(i.status.as_str())),
+        Column::Reason => per_isa(row.infos, isa, |i| i.reason.as_deref()),
+        Column::Isa => row
+            .infos
+            .iter()
+            .fold(Vec::<String>::new(), |mut isas, i| {
+                let name = if i.primary_abi { format!("{}*", i.isa) } else { i.isa.clone() };
+                if !isas.contains(&name) {
+                    isas.push(name);
+                }
+                isas
+            })
+            .join(","),
+        Column::Size => row.size.map(format_bytes).unwrap_or_else(|| "-".to_string()),
+        Column::Origin => row.origin.to_string(),
+        Column::Installer => row.installer.unwrap_or("-").to_string(),
+        Column::LastDexopt => match row.compilation {
+            Some(Compilation { freshness: Freshness::Missing, .. }) => Freshness::Missing.to_string(),
+            Some(Compilation { last_dexopt, freshness }) => {
+                let age = UI::format_last_dexopt(last_dexopt);
+                if freshness == Freshness::Stale { format!("{} (stale)", age) } else { age }
+            }
+            None => "-".to_string(),
+        },
+    }
+}
+
+/// Cuts `text` to `width` display columns, ending in an ellipsis when something was cut.
+fn truncate(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return text.to_string();
+    }
+    let ellipsis = theme::glyphs().ellipsis;
+    let mut out = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w + ellipsis.width() > width {
+            break;
+        }
+        out.push(c);
+        used += w;
+    }
+    out.push_str(ellipsis);
+    out
+}
+
+fn pad(text: &str, width: usize) -> String {
+    format!("{}{}", text, " ".repeat(width.saturating_sub(text.width())))
+}
+
+fn style(leaf: &Leaf, text: &str, value: &str, highlight: bool) -> String {
+    match leaf.column {
+        Column::Name if highlight => text.black().on_bright_yellow().to_string(),
+        Column::Name => text.bright_white().to_string(),
+        Column::Status if value.split('/').count() == 1 => {
+            let colored = text.color(UI::get_status_color(value));
+            if value == "error" { colored.bold().to_string() } else { colored.to_string() }
+        }
+        Column::Reason | Column::Size | Column::Isa => text.dimmed().to_string(),
+        Column::LastDexopt if value.ends_with("(stale)") || value == "missing" => text.red().to_string(),
+        _ => text.to_string(),
+    }
+}
+
+/// Renders the rows as lines; `max_width` (the terminal width) makes wide columns truncate.
+pub fn render(rows: &[Row], columns: &[Column], max_width: Option<usize>) -> Vec<String> {
+    // ISAs of the displayed packages, 64-bit first
+    let mut isas: Vec<&str> = Vec::new();
+    for info in rows.iter().flat_map(|r| r.infos) {
+        if !isas.contains(&info.isa.as_str()) {
+            isas.push(&info.isa);
+        }
+    }
+    isas.sort_by_key(|isa| (!isa.contains("64"), *isa));
+    let split = isas.len() > 1;
+
+    let mut leaves: Vec<Leaf> = Vec::new();
+    for &column in columns {
+        if split && column.is_per_isa() {
+            leaves.extend(isas.iter().map(|isa| Leaf { column, isa: Some(isa.to_string()), width: 0 }));
+        } else {
+            leaves.push(Leaf { column, isa: None, width: 0 });
+        }
+    }
+
+    let cells: Vec<Vec<String>> = rows.iter().map(|row| leaves.iter().map(|leaf| cell(row, leaf)).collect()).collect();
+    for (i, leaf) in leaves.iter_mut().enumerate() {
+        let header = leaf.isa.as_deref().map_or(leaf.column.title().width(), str::width);
+        leaf.width = cells.iter().map(|c| c[i].width()).chain([header]).max().unwrap_or(0);
+    }
+
+    if let Some(max_width) = max_width {
+        let total = |leaves: &[Leaf]| {
+            leaves.iter().map(|l| l.width).sum::<usize>() + SEPARATOR.len() * leaves.len().saturating_sub(1)
+        };
+        // Shrink the widest flexible column first, then any column, one character at a time
+        while total(&leaves) > max_width {
+            let widest = |leaves: &[Leaf], flexible_only: bool| {
+                (0..leaves.len())
+                    .filter(|&i| (!flexible_only || leaves[i].column.is_flexible()) && leaves[i].width > MIN_WIDTH)
+                    .max_by_key(|&i| leaves[i].width)
+            };
+            match widest(&leaves, true).or_else(|| widest(&leaves, false)) {
+                Some(i) => leaves[i].width -= 1,
+                None => break,
+            }
+        }
+    }
+
+    let mut lines = Vec::new();
+
+    // Per-ISA columns get their title on a line of its own, spanning their sub-columns
+    if split && columns.iter().any(|c| c.is_per_isa()) {
+        let mut spans: Vec<String> = Vec::new();
+        let mut i = 0;
+        while i < leaves.len() {
+            let column = leaves[i].column;
+            let count = if column.is_per_isa() { isas.len() } else { 1 };
+            let width: usize = leaves[i..i + count].iter().map(|l| l.width).sum::<usize>() + SEPARATOR.len() * (count - 1);
+            let title = if column.is_per_isa() { truncate(column.title(), width) } else { String::new() };
+            spans.push(pad(&title, width).bold().to_string());
+            i += count;
+        }
+        lines.push(spans.join(SEPARATOR).trim_end().to_string());
+    }
+
+    let header: Vec<String> = leaves
+        .iter()
+        .map(|leaf| {
+            let title = leaf.isa.as_deref().unwrap_or(leaf.column.title());
+            pad(&truncate(title, leaf.width), leaf.width).bold().underline().to_string()
+        })
+        .collect();
+    lines.push(header.join(SEPARATOR));
+
+    for (row, cells) in rows.iter().zip(&cells) {
+        if let Some(ref group) = row.group {
+            lines.push(String::new());
+            lines.push(format!("{} {}", theme::glyphs().group.bright_blue(), group.bold().color(UI::get_status_color(group.split(' ').next().unwrap_or_default()))));
+        }
+        let line: Vec<String> = leaves
+            .iter()
+            .zip(cells)
+            .map(|(leaf, value)| {
+                let text = pad(&truncate(value, leaf.width), leaf.width);
+                style(leaf, &text, value, row.highlight)
+            })
+            .collect();
+        lines.push(line.join(SEPARATOR).trim_end().to_string());
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(isa: &str, status: &str) -> DexOptInfo {
+        DexOptInfo {
+            raw_line: String::new(),
+            isa: isa.into(),
+            status: status.into(),
+            reason: Some("install".into()),
+            primary_abi: isa == "arm64",
+            dex_path: None,
+            location: None,
+        }
+    }
+
+    #[test]
+    fn test_render_fits_width() {
+        colored::control::set_override(false);
+        let pkg = Package { name: "com.example.a.very.long.package.name".into(), path: String::new() };
+        let infos = vec![info("arm64", "speed-profile"), info("arm", "verify")];
+        let rows = [Row { pkg: &pkg, label: None, infos: &infos, size: Some(2048), origin: Origin::Data, installer: None, compilation: None, group: None, highlight: false }];
+
+        let lines = render(&rows, &[Column::Name, Column::Status, Column::Size], Some(40));
+        assert_eq!(lines[0], "          Status");
+        assert_eq!(lines[1], "Package   arm64          arm     Size   ");
+        assert_eq!(lines[2], "com.exa…  speed-profile  verify  2.0 KiB");
+        assert!(lines.iter().all(|l| l.width() <= 40));
+
+        assert_eq!(truncate("日本語テキスト", 7), "日本語…");
+    }
+}
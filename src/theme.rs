@@ -0,0 +1,215 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use colored::Color;
+use once_cell::sync::OnceCell;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// When to emit ANSI colors.
+#[derive(Copy, Clone, PartialEq, Eq, Default, ValueEnum, Debug)]
+pub enum ColorChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// `always` and `never` win; `auto` follows `CLICOLOR_FORCE`, then `NO_COLOR`, then whether stdout is a terminal.
+    pub fn enabled(self, var: impl Fn(&str) -> Option<String>, is_terminal: bool) -> bool {
+        let set = |name: &str| var(name).is_some_and(|v| !v.is_empty() && v != "0");
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto if set("CLICOLOR_FORCE") => true,
+            // https://no-color.org: any non-empty value disables color
+            ColorChoice::Auto if var("NO_COLOR").is_some_and(|v| !v.is_empty()) => false,
+            ColorChoice::Auto => is_terminal,
+        }
+    }
+}
+
+/// Characters used for boxes, markers and bars, so `--ascii` can swap them all at once.
+pub struct Glyphs {
+    pub top_left: &'static str,
+    pub top_right: &'static str,
+    pub bottom_left: &'static str,
+    pub bottom_right: &'static str,
+    pub horizontal: &'static str,
+    pub vertical: &'static str,
+    pub double_top_left: &'static str,
+    pub double_top_right: &'static str,
+    pub double_bottom_left: &'static str,
+    pub double_bottom_right: &'static str,
+    pub double_left_tee: &'static str,
+    pub double_right_tee: &'static str,
+    pub double_horizontal: &'static str,
+    pub double_vertical: &'static str,
+    pub group: &'static str,
+    pub arrow: &'static str,
+    pub ellipsis: &'static str,
+    pub separator: &'static str,
+    pub check: &'static str,
+    pub cross: &'static str,
+    pub bar_full: &'static str,
+    pub bar_empty: &'static str,
+    pub up_down: &'static str,
+    pub cursor: &'static str,
+}
+
+pub const UNICODE: Glyphs = Glyphs {
+    top_left: "┌",
+    top_right: "┐",
+    bottom_left: "└",
+    bottom_right: "┘",
+    horizontal: "─",
+    vertical: "│",
+    double_top_left: "╔",
+    double_top_right: "╗",
+    double_bottom_left: "╚",
+    double_bottom_right: "╝",
+    double_left_tee: "╠",
+    double_right_tee: "╣",
+    double_horizontal: "═",
+    double_vertical: "║",
+    group: "▸",
+    arrow: "→",
+    ellipsis: "…",
+    separator: "·",
+    check: "✓",
+    cross: "✗",
+    bar_full: "█",
+    bar_empty: "░",
+    up_down: "↑↓",
+    cursor: "█",
+};
+
+pub const ASCII: Glyphs = Glyphs {
+    top_left: "+",
+    top_right: "+",
+    bottom_left: "+",
+    bottom_right: "+",
+    horizontal: "-",
+    vertical: "|",
+    double_top_left: "+",
+    double_top_right: "+",
+    double_bottom_left: "+",
+    double_bottom_right: "+",
+    double_left_tee: "+",
+    double_right_tee: "+",
+    double_horizontal: "=",
+    double_vertical: "|",
+    group: ">",
+    arrow: "->",
+    ellipsis: "...",
+    separator: "|",
+    check: "OK",
+    cross: "x",
+    bar_full: "#",
+    bar_empty: ".",
+    up_down: "up/down",
+    cursor: "_",
+};
+
+/// Status colors overriding the built-in ones, e.g. in TOML:
+///
+/// ```toml
+/// [status]
+/// speed-profile = "bright green"
+/// verify = "#ffaf00"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Theme {
+    #[serde(default)]
+    status: HashMap<String, ThemeColor>,
+}
+
+/// A color name as accepted by `colored` (`red`, `bright blue`, `#rrggbb`, ...).
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(try_from = "String")]
+struct ThemeColor(Color);
+
+impl TryFrom<String> for ThemeColor {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        name.parse().map(ThemeColor).map_err(|_| {
+            format!("unknown color '{}', expected a name such as 'green' or 'bright blue', or '#rrggbb'", name)
+        })
+    }
+}
+
+impl Theme {
+    /// Loads a theme, picking the format from the extension (`.toml`, `.yaml`, `.yml`).
+    pub fn load(path: &Path) -> Result<Self> {
+        let raw = fs::read_to_string(path).with_context(|| format!("Failed to read theme '{}'", path.display()))?;
+        let theme: Theme = match path.extension().and_then(|e| e.to_str()) {
+            Some("yaml" | "yml") => serde_yaml::from_str(&raw).with_context(|| format!("Invalid theme '{}'", path.display()))?,
+            _ => toml::from_str(&raw).with_context(|| format!("Invalid theme '{}'", path.display()))?,
+        };
+        Ok(theme)
+    }
+
+    /// `$XDG_CONFIG_HOME/dexter/theme.toml`, falling back to `~/.config/dexter/theme.toml`.
+    pub fn default_path() -> Option<PathBuf> {
+        let config = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config.join("dexter").join("theme.toml"))
+    }
+}
+
+static GLYPHS: OnceCell<&'static Glyphs> = OnceCell::new();
+static THEME: OnceCell<Theme> = OnceCell::new();
+static COLOR: OnceCell<ColorChoice> = OnceCell::new();
+
+/// Sets the glyphs, color choice and theme for the rest of the run; later calls are ignored.
+pub fn init(ascii: bool, color: ColorChoice, theme: Theme) {
+    let _ = GLYPHS.set(if ascii { &ASCII } else { &UNICODE });
+    let _ = COLOR.set(color);
+    let _ = THEME.set(theme);
+}
+
+pub fn glyphs() -> &'static Glyphs {
+    GLYPHS.get().copied().unwrap_or(&UNICODE)
+}
+
+/// Whether screen clearing and line rewriting may be sent to a stream: only to a terminal,
+/// and never with `--color never`.
+pub fn cursor_control(is_terminal: bool) -> bool {
+    is_terminal && COLOR.get() != Some(&ColorChoice::Never)
+}
+
+/// The theme's color for `status`, if it sets one.
+pub fn status_color(status: &str) -> Option<Color> {
+    THEME.get()?.status.get(status).map(|c| c.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_choice() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| vars.iter().find(|(k, _)| *k == name).map(|(_, v)| v.to_string())
+        };
+        assert!(ColorChoice::Auto.enabled(env(&[]), true));
+        assert!(!ColorChoice::Auto.enabled(env(&[]), false));
+        assert!(!ColorChoice::Auto.enabled(env(&[("NO_COLOR", "1")]), true));
+        assert!(ColorChoice::Auto.enabled(env(&[("NO_COLOR", "")]), true));
+        assert!(ColorChoice::Auto.enabled(env(&[("NO_COLOR", "1"), ("CLICOLOR_FORCE", "1")]), false));
+        assert!(!ColorChoice::Auto.enabled(env(&[("CLICOLOR_FORCE", "0")]), false));
+        assert!(ColorChoice::Always.enabled(env(&[("NO_COLOR", "1")]), false));
+        assert!(!ColorChoice::Never.enabled(env(&[("CLICOLOR_FORCE", "1")]), true));
+
+        let theme: Theme = toml::from_str("[status]\nverify = \"bright yellow\"\nspeed = \"#00ff00\"\n").unwrap();
+        assert_eq!(theme.status["verify"].0, Color::BrightYellow);
+        assert_eq!(theme.status["speed"].0, Color::TrueColor { r: 0, g: 255, b: 0 });
+        assert!(toml::from_str::<Theme>("[status]\nverify = \"amber\"\n").is_err());
+    }
+}
@@ -0,0 +1,563 @@
+use crate::compiler_filter::CompilerFilter;
+use crate::sorting;
+use crate::theme;
+use crate::{Analyzer, ApkDetails, AppType, DexOptInfo, Filters, Package, PackageAction, ProfileState, UI};
+use anyhow::Result;
+use colored::Colorize;
+use rayon::prelude::*;
+use ratatui::DefaultTerminal;
+use ratatui::Frame;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Flex, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::symbols::border;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Cell, Clear, Paragraph, Row, Table, TableState, Wrap};
+use std::collections::HashMap;
+
+struct Entry {
+    pkg: Package,
+    label: Option<String>,
+    infos: Vec<DexOptInfo>,
+    status: String,
+    /// Whether the package passes the command-line filters, re-evaluated with each dump.
+    in_scope: bool,
+}
+
+impl Entry {
+    /// Optimization level of the primary entry, for sorting.
+    fn level(&self) -> Option<(Option<CompilerFilter>, &str)> {
+        sorting::status_level(&self.infos)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum SortKey {
+    Name,
+    Label,
+    Status,
+}
+
+impl SortKey {
+    fn next(self) -> Self {
+        match self {
+            SortKey::Name => SortKey::Label,
+            SortKey::Label => SortKey::Status,
+            SortKey::Status => SortKey::Name,
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Label => "label",
+            SortKey::Status => "status",
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Mode {
+    Browse,
+    Search,
+    Confirm(PackageAction),
+}
+
+struct App {
+    app_type: AppType,
+    /// Command-line filters other than the name, which lives in the search box.
+    scope: Filters,
+    entries: Vec<Entry>,
+    /// Indices into `entries` that pass the search, in display order.
+    visible: Vec<usize>,
+    table: TableState,
+    query: String,
+    sort: SortKey,
+    reverse: bool,
+    mode: Mode,
+    details: HashMap<String, Option<ApkDetails>>,
+    /// Profile state of the selected package, refreshed when the selection or dump changes.
+    profile: Option<(String, ProfileState)>,
+    message: Option<String>,
+}
+
+/// A bordered block drawn with the `--ascii`-aware glyphs.
+fn bordered(title: &str) -> Block<'_> {
+    let g = theme::glyphs();
+    Block::bordered()
+        .border_set(border::Set {
+            top_left: g.top_left,
+            top_right: g.top_right,
+            bottom_left: g.bottom_left,
+            bottom_right: g.bottom_right,
+            vertical_left: g.vertical,
+            vertical_right: g.vertical,
+            horizontal_top: g.horizontal,
+            horizontal_bottom: g.horizontal,
+        })
+        .title(title)
+}
+
+/// Converts the CLI status colors so both front ends agree.
+fn to_tui_color(color: colored::Color) -> Color {
+    match color {
+        colored::Color::Green => Color::Green,
+        colored::Color::Yellow => Color::Yellow,
+        colored::Color::Blue => Color::Blue,
+        colored::Color::Red => Color::Red,
+        colored::Color::Magenta => Color::Magenta,
+        colored::Color::Cyan => Color::Cyan,
+        colored::Color::BrightBlack => Color::DarkGray,
+        colored::Color::BrightRed => Color::LightRed,
+        colored::Color::BrightGreen => Color::LightGreen,
+        colored::Color::BrightYellow => Color::LightYellow,
+        colored::Color::BrightBlue => Color::LightBlue,
+        colored::Color::BrightMagenta => Color::LightMagenta,
+        colored::Color::BrightCyan => Color::LightCyan,
+        colored::Color::TrueColor { r, g, b } => Color::Rgb(r, g, b),
+        _ => Color::Reset,
+    }
+}
+
+fn status_style(status: &str) -> Style {
+    let style = Style::new().fg(to_tui_color(UI::get_status_color(status)));
+    if status == "error" { style.add_modifier(Modifier::BOLD) } else { style }
+}
+
+/// Whether any entry's status starts with `prefix`; the search box is typed a character at a
+/// time, so `status:spe` already finds `speed` and `speed-profile`.
+fn status_starts_with(infos: &[DexOptInfo], prefix: &str) -> bool {
+    infos.iter().any(|i| i.status.starts_with(prefix))
+}
+
+/// Parses the search box: `status:<s>` (or `s:<s>`) sets the status prefix, other words the name filter.
+fn parse_query(query: &str) -> Filters {
+    let mut filters = Filters::default();
+    let mut words = Vec::new();
+
+    for word in query.split_whitespace() {
+        match word.split_once(':') {
+            Some(("status" | "s", status)) if !status.is_empty() => filters.status = Some(status.to_string()),
+            _ => words.push(word),
+        }
+    }
+    if !words.is_empty() {
+        filters.name = Some(words.join(" "));
+    }
+    filters
+}
+
+/// Starts the interactive browser; the name filter pre-fills the search box, the others (including
+/// the exact `--status`) limit which packages are listed at all.
+pub fn run(app_type: AppType, mut filters: Filters) -> Result<()> {
+    let query = filters.name.take().unwrap_or_default();
+
+    println!("{} Loading packages ({}) ...", Colorize::cyan("[-]"), app_type);
+    let mut app = App {
+        app_type,
+        scope: filters,
+        entries: Vec::new(),
+        visible: Vec::new(),
+        table: TableState::default(),
+        query,
+        sort: SortKey::Name,
+        reverse: false,
+        mode: Mode::Browse,
+        details: HashMap::new(),
+        profile: None,
+        message: None,
+    };
+    app.load()?;
+
+    let mut terminal = ratatui::init();
+    let result = app.event_loop(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+impl App {
+    /// Fetches the package list, labels and dexopt dump from scratch.
+    fn load(&mut self) -> Result<()> {
+        let packages = Package::fetch_list(self.app_type)?;
+        let analyzer = Analyzer::new(&Analyzer::fetch_dump()?);
+
+        self.entries = packages
+            .into_par_iter()
+            .map(|pkg| {
+                let label = pkg.get_label();
+                Entry { pkg, label, infos: Vec::new(), status: String::new(), in_scope: false }
+            })
+            .collect();
+        self.apply_dump(&analyzer);
+        Ok(())
+    }
+
+    /// Refreshes only the dexopt state, keeping labels and the selection.
+    fn reload_dump(&mut self) -> Result<()> {
+        let analyzer = Analyzer::new(&Analyzer::fetch_dump()?);
+        self.apply_dump(&analyzer);
+        Ok(())
+    }
+
+    fn apply_dump(&mut self, analyzer: &Analyzer) {
+        let scope = &self.scope;
+        self.entries.par_iter_mut().for_each(|entry| {
+            let infos = analyzer.get_info(&entry.pkg.name);
+            entry.in_scope = scope.matches(&entry.pkg, infos);
+            entry.infos = infos.cloned().unwrap_or_default();
+            entry.status = analyzer.status_of(&entry.pkg.name).unwrap_or_else(|| "(no info)".to_string());
+        });
+        // Actions change profiles, so they are read again
+        self.profile = None;
+        self.refilter();
+    }
+
+    fn selected(&self) -> Option<&Entry> {
+        self.table.selected().and_then(|i| self.visible.get(i)).map(|&i| &self.entries[i])
+    }
+
+    fn refilter(&mut self) {
+        let selected_name = self.selected().map(|e| e.pkg.name.clone());
+        let filters = parse_query(&self.query);
+
+        let mut visible: Vec<usize> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.in_scope)
+            .filter(|(_, e)| {
+                let name_matches = filters.matches_name(&e.pkg.name)
+                    || e.label.as_deref().is_some_and(|l| filters.matches_name(l));
+                name_matches && filters.status.as_deref().is_none_or(|s| status_starts_with(&e.infos, s))
+            })
+            .map(|(i, _)| i)
+            .collect();
+
+        let entries = &self.entries;
+        visible.sort_by(|&a, &b| {
+            let (a, b) = (&entries[a], &entries[b]);
+            let ordering = match self.sort {
+                SortKey::Name => a.pkg.name.cmp(&b.pkg.name),
+                SortKey::Label => a.label.as_deref().unwrap_or(&a.pkg.name).to_lowercase()
+                    .cmp(&b.label.as_deref().unwrap_or(&b.pkg.name).to_lowercase()),
+                SortKey::Status => a.level().cmp(&b.level()).then_with(|| a.pkg.name.cmp(&b.pkg.name)),
+            };
+            if self.reverse { ordering.reverse() } else { ordering }
+        });
+        self.visible = visible;
+
+        // Keep the same package selected when possible
+        let position = selected_name
+            .and_then(|name| self.visible.iter().position(|&i| self.entries[i].pkg.name == name))
+            .unwrap_or(0);
+        self.table.select((!self.visible.is_empty()).then_some(position));
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        if self.visible.is_empty() {
+            return;
+        }
+        let current = self.table.selected().unwrap_or(0) as isize;
+        let next = (current + delta).clamp(0, self.visible.len() as isize - 1);
+        self.table.select(Some(next as usize));
+    }
+
+    fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        loop {
+            terminal.draw(|f| self.draw(f))?;
+
+            let Event::Key(key) = event::read()? else { continue };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            // Raw mode turns Ctrl-C into a key press, so it would otherwise end up in the search box
+            if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+                return Ok(());
+            }
+
+            match self.mode {
+                Mode::Search => match key.code {
+                    KeyCode::Enter => self.mode = Mode::Browse,
+                    KeyCode::Esc => {
+                        self.query.clear();
+                        self.mode = Mode::Browse;
+                        self.refilter();
+                    }
+                    KeyCode::Backspace => {
+                        self.query.pop();
+                        self.refilter();
+                    }
+                    KeyCode::Char(c) => {
+                        self.query.push(c);
+                        self.refilter();
+                    }
+                    _ => {}
+                },
+                Mode::Confirm(action) => {
+                    self.mode = Mode::Browse;
+                    if matches!(key.code, KeyCode::Char('y') | KeyCode::Char('Y')) {
+                        self.run_action(action, terminal)?;
+                    } else {
+                        self.message = Some("Cancelled.".to_string());
+                    }
+                }
+                Mode::Browse => {
+                    self.message = None;
+                    match key.code {
+                        KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                        KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+                        KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+                        KeyCode::PageDown => self.move_selection(20),
+                        KeyCode::PageUp => self.move_selection(-20),
+                        KeyCode::Home | KeyCode::Char('g') => self.move_selection(isize::MIN / 2),
+                        KeyCode::End | KeyCode::Char('G') => self.move_selection(isize::MAX / 2),
+                        KeyCode::Char('/') => self.mode = Mode::Search,
+                        KeyCode::Char('s') => {
+                            self.sort = self.sort.next();
+                            self.refilter();
+                        }
+                        KeyCode::Char('r') => {
+                            self.reverse = !self.reverse;
+                            self.refilter();
+                        }
+                        KeyCode::Char('u') => {
+                            self.reload_dump()?;
+                            self.message = Some("Dexopt state refreshed.".to_string());
+                        }
+                        KeyCode::Char('o') if self.selected().is_some() => {
+                            self.mode = Mode::Confirm(PackageAction::Optimize)
+                        }
+                        KeyCode::Char('x') if self.selected().is_some() => {
+                            self.mode = Mode::Confirm(PackageAction::Reset)
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    fn run_action(&mut self, action: PackageAction, terminal: &mut DefaultTerminal) -> Result<()> {
+        let Some(name) = self.selected().map(|e| e.pkg.name.clone()) else {
+            return Ok(());
+        };
+        let verb = match action {
+            PackageAction::Optimize => "Optimizing",
+            PackageAction::Reset => "Resetting",
+        };
+        self.message = Some(format!("{} {} ...", verb, name));
+        terminal.draw(|f| self.draw(f))?;
+
+        let failures = action.run(&name)?;
+        self.reload_dump()?;
+        self.message = Some(if failures.is_empty() {
+            format!("{} {}: done.", verb, name)
+        } else {
+            failures.join("; ")
+        });
+        Ok(())
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [title_area, main_area, footer_area] =
+            Layout::vertical([Constraint::Length(1), Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [list_area, detail_area] =
+            Layout::horizontal([Constraint::Percentage(55), Constraint::Percentage(45)]).areas(main_area);
+
+        let g = theme::glyphs();
+        let title = format!(
+            " dexter {sep} {} apps {sep} {}/{} shown {sep} sort: {}{}",
+            self.app_type,
+            self.visible.len(),
+            self.entries.len(),
+            self.sort.title(),
+            if self.reverse { " (reversed)" } else { "" },
+            sep = g.separator
+        );
+        frame.render_widget(Paragraph::new(title).bold().reversed(), title_area);
+
+        self.draw_list(frame, list_area);
+        self.draw_details(frame, detail_area);
+        self.draw_footer(frame, footer_area);
+
+        if let Mode::Confirm(action) = self.mode
+            && let Some(entry) = self.selected()
+        {
+            let question = match action {
+                PackageAction::Optimize => "Clear profiles and compile with 'speed'",
+                PackageAction::Reset => "Reset compiled code to the install-time state",
+            };
+            let text = vec![
+                Line::from(question),
+                Line::from(entry.pkg.name.clone().bold()),
+                Line::from(""),
+                Line::from("[y] confirm   any other key cancels".dim()),
+            ];
+
+            let [area] = Layout::vertical([Constraint::Length(6)]).flex(Flex::Center).areas(frame.area());
+            let [area] = Layout::horizontal([Constraint::Length(60)]).flex(Flex::Center).areas(area);
+            frame.render_widget(Clear, area);
+            frame.render_widget(
+                Paragraph::new(text).centered().block(bordered(" Confirm ").yellow()),
+                area,
+            );
+        }
+    }
+
+    fn draw_list(&mut self, frame: &mut Frame, area: Rect) {
+        let rows = self.visible.iter().map(|&i| {
+            let e = &self.entries[i];
+            Row::new(vec![
+                Cell::from(e.pkg.name.clone()),
+                Cell::from(e.label.clone().unwrap_or_default()).cyan(),
+                Cell::from(e.status.clone()).style(status_style(e.status.split('/').next().unwrap_or(""))),
+            ])
+        });
+
+        let table = Table::new(
+            rows,
+            [Constraint::Percentage(50), Constraint::Percentage(25), Constraint::Percentage(25)],
+        )
+        .header(Row::new(["Package", "Label", "Status"]).bold().underlined())
+        .row_highlight_style(Style::new().reversed())
+        .block(bordered(" Packages "));
+
+        frame.render_stateful_widget(table, area, &mut self.table);
+    }
+
+    fn draw_details(&mut self, frame: &mut Frame, area: Rect) {
+        let block = bordered(" Details ");
+        let Some(name) = self.selected().map(|e| e.pkg.name.clone()) else {
+            frame.render_widget(Paragraph::new("No package selected.").block(block), area);
+            return;
+        };
+
+        // Manifests are parsed lazily, once per package
+        if !self.details.contains_key(&name) {
+            let details = self.selected().and_then(|e| e.pkg.get_apk_details());
+            self.details.insert(name.clone(), details);
+        }
+        // Profiles change while the app runs, so only the selected one is kept
+        if self.profile.as_ref().is_none_or(|(cached, _)| *cached != name) {
+            let profile = self.selected().map(|e| e.pkg.get_profile_state()).unwrap_or_default();
+            self.profile = Some((name.clone(), profile));
+        }
+        let Some(entry) = self.selected() else { return };
+        let details = self.details.get(&name).and_then(Option::as_ref);
+
+        let field = |label: &str, value: String| {
+            Line::from(vec![Span::from(format!("{:<10}", label)).bold(), Span::from(value)])
+        };
+        let yes_no = |b: bool| if b { "yes" } else { "no" };
+
+        let mut lines = vec![
+            Line::from(entry.label.clone().unwrap_or_else(|| entry.pkg.name.clone())).cyan().bold(),
+            Line::from(entry.pkg.name.clone()).bold(),
+            Line::from(""),
+            field("Path", entry.pkg.path.clone()),
+        ];
+        if let Some(d) = details {
+            lines.push(field(
+                "Version",
+                format!(
+                    "{} ({})",
+                    d.version_name.as_deref().unwrap_or("?"),
+                    d.version_code.as_deref().unwrap_or("?")
+                ),
+            ));
+            lines.push(field(
+                "SDK",
+                format!("min {} / target {}", d.min_sdk.as_deref().unwrap_or("?"), d.target_sdk),
+            ));
+            lines.push(field(
+                "Flags",
+                format!(
+                    "debuggable={} vmSafeMode={} hasCode={}",
+                    yes_no(d.flags.debuggable),
+                    yes_no(d.flags.vm_safe_mode),
+                    yes_no(d.flags.has_code)
+                ),
+            ));
+        } else {
+            lines.push(field("Manifest", "unreadable".to_string()));
+        }
+
+        let profile = self.profile.as_ref().map(|(_, p)| p.clone()).unwrap_or_default();
+        lines.push(field(
+            "Profiles",
+            format!(
+                "current={} reference={} dm={}",
+                yes_no(profile.current),
+                yes_no(profile.reference),
+                yes_no(profile.dex_metadata)
+            ),
+        ));
+
+        lines.push(Line::from(""));
+        lines.push(Line::from("DexOpt").bold().underlined());
+        if entry.infos.is_empty() {
+            lines.push(Line::from("(no info found)").red().italic());
+        }
+        for info in &entry.infos {
+            lines.push(Line::from(info.raw_line.clone()).style(status_style(&info.status)));
+            if let Some(ref location) = info.location {
+                lines.push(Line::from(format!("  {}", location)).dim());
+            }
+        }
+
+        frame.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }).block(block), area);
+    }
+
+    fn draw_footer(&self, frame: &mut Frame, area: Rect) {
+        let g = theme::glyphs();
+        let footer = match self.mode {
+            Mode::Search => Line::from(vec![
+                Span::from("/").yellow().bold(),
+                Span::from(self.query.clone()),
+                Span::from(g.cursor).dim(),
+                Span::from(format!("   name or label, 'status:<prefix>' {sep} Enter keep {sep} Esc clear", sep = g.separator)).dim(),
+            ]),
+            _ => match self.message {
+                Some(ref message) => Line::from(message.clone()).yellow(),
+                None => Line::from(format!(
+                    "{}/jk move {sep} / search {sep} s sort {sep} r reverse {sep} o optimize {sep} x reset {sep} u refresh {sep} q quit",
+                    g.up_down,
+                    sep = g.separator
+                ))
+                .dim(),
+            },
+        };
+        frame.render_widget(Paragraph::new(footer), area);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_query() {
+        let filters = parse_query("google status:verify");
+        assert_eq!(filters.name.as_deref(), Some("google"));
+        assert_eq!(filters.status.as_deref(), Some("verify"));
+
+        let filters = parse_query("s:error");
+        assert!(filters.name.is_none());
+        assert_eq!(filters.status.as_deref(), Some("error"));
+
+        let info = |status: &str| DexOptInfo {
+            raw_line: String::new(),
+            isa: "arm64".into(),
+            status: status.into(),
+            reason: None,
+            primary_abi: true,
+            dex_path: None,
+            location: None,
+        };
+        let infos = [info("verify"), info("speed-profile")];
+        assert!(status_starts_with(&infos, "spe"));
+        assert!(status_starts_with(&infos, "speed-profile"));
+        assert!(!status_starts_with(&infos, "speed-profile-x"));
+        assert!(!status_starts_with(&[], "verify"));
+    }
+}